@@ -1,5 +1,7 @@
-use actix_web::{web, HttpResponse, Scope};
+use actix_web::{web, HttpResponse};
+use crate::models::error_model::ApiErrorType;
 use crate::services::aggregator_service::AggregatorService;
+use crate::telemetry;
 
 pub fn init_aggregator_api(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,9 +10,9 @@ pub fn init_aggregator_api(cfg: &mut web::ServiceConfig) {
     );
 }
 
-async fn aggregate(service: web::Data<AggregatorService>) -> HttpResponse {
-    match service.fetch_data().await {
-        Ok(data) => HttpResponse::Ok().json(data),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to fetch aggregated data"),
+async fn aggregate(service: web::Data<AggregatorService>) -> Result<HttpResponse, ApiErrorType> {
+    match telemetry::traced("aggregator_service.fetch_data", service.fetch_data()).await {
+        Ok(data) => Ok(HttpResponse::Ok().json(data)),
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
\ No newline at end of file
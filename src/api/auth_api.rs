@@ -2,11 +2,13 @@ use actix_web::web::Data;
 use actix_web::{get, post, put, web, web::Json, HttpResponse};
 use log::info;
 use log::warn;
-use mongodb::Client;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-use crate::{models::error_model::ApiErrorType, services::auth_service};
+use crate::{
+    config::settings::Settings, models::error_model::ApiErrorType,
+    repository::auth_repo::AuthRepository, services::auth_service, telemetry,
+};
 
 // -- configurations
 pub fn init(cfg: &mut web::ServiceConfig) {
@@ -87,14 +89,20 @@ pub struct ForgotPasswordRequest {
 
 // -- Controllers...
 // Register a user.
-#[post("/a/register")]
+#[post("/register")]
 pub async fn auth_register(
-    client: Data<Client>,
+    repo: Data<dyn AuthRepository>,
     register_user: Json<RegisterRequest>,
 ) -> Result<HttpResponse, ApiErrorType> {
     // Step 1: Validate payload.
     match register_user.validate() {
-        Ok(_) => auth_service::create_user(&client, register_user.0).await,
+        Ok(_) => {
+            telemetry::traced(
+                "auth_service.create_user",
+                auth_service::create_user(repo.get_ref(), register_user.0),
+            )
+            .await
+        }
         Err(err) => {
             warn!("Error: {}", err);
             // Validation error.
@@ -104,14 +112,21 @@ pub async fn auth_register(
 }
 
 // Login using credentials.
-#[post("/a/login")]
+#[post("/login")]
 pub async fn auth_login(
-    client: Data<Client>,
+    repo: Data<dyn AuthRepository>,
+    settings: Data<Settings>,
     login_user: Json<LoginRequest>,
 ) -> Result<HttpResponse, ApiErrorType> {
     // Step 1: Validate payload.
     match login_user.validate() {
-        Ok(_) => auth_service::login(&client, login_user.0).await,
+        Ok(_) => {
+            telemetry::traced(
+                "auth_service.login",
+                auth_service::login(repo.get_ref(), &settings.jwt, login_user.0),
+            )
+            .await
+        }
         Err(err) => {
             warn!("Error: {}", err);
             Err(ApiErrorType::BadRequest)
@@ -120,19 +135,19 @@ pub async fn auth_login(
 }
 
 // Update password for an existing user with credentials.
-#[put("/a/password")]
+#[put("/password")]
 pub async fn update_password(update_password: Json<UpdatePasswordRequest>) -> HttpResponse {
     HttpResponse::Ok().json(update_password)
 }
 
 // Forgett password flow.
-#[post("/a/forgot-password")]
+#[post("/forgot-password")]
 pub async fn forgot_password(forgot_password: Json<ForgotPasswordRequest>) -> HttpResponse {
     HttpResponse::Ok().json(forgot_password)
 }
 
 // Logout user.
-#[get("/a/logout")]
+#[get("/logout")]
 pub async fn logout(path: web::Path<String>) -> HttpResponse {
     info!("{}", path);
     HttpResponse::NoContent().finish()
@@ -0,0 +1,21 @@
+use actix_web::{get, web, web::Data, HttpResponse};
+
+use crate::config::settings::Settings;
+use crate::services::health_service::{self, HealthChecks};
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(live);
+    cfg.service(ready);
+}
+
+// Liveness probe, answers as long as the process serves requests.
+#[get("/health/live")]
+pub async fn live() -> HttpResponse {
+    health_service::live()
+}
+
+// Readiness probe, checks every registered dependency.
+#[get("/health/ready")]
+pub async fn ready(health: Data<HealthChecks>, settings: Data<Settings>) -> HttpResponse {
+    health_service::ready(&health, &settings.health, &settings.errors).await
+}
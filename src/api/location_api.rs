@@ -1,5 +1,6 @@
 use crate::models::error_model::ApiErrorType;
 use crate::services::location_service;
+use crate::telemetry;
 use actix_web::{get, web, HttpResponse};
 
 pub fn init(cfg: &mut web::ServiceConfig) {
@@ -8,5 +9,5 @@ pub fn init(cfg: &mut web::ServiceConfig) {
 
 #[get("/locations")]
 pub async fn get_location() -> Result<HttpResponse, ApiErrorType> {
-    location_service::get_location().await
+    telemetry::traced("location_service.get_location", location_service::get_location()).await
 }
@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::metrics;
+use crate::models::error_model::ApiErrorType;
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_metrics);
+}
+
+// Every metric in the Prometheus text format, for scraping.
+#[get("/metrics")]
+pub async fn get_metrics() -> Result<HttpResponse, ApiErrorType> {
+    match metrics::render() {
+        Ok(body) => Ok(HttpResponse::Ok()
+            .content_type(prometheus::TEXT_FORMAT)
+            .body(body)),
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
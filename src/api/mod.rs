@@ -1,15 +1,21 @@
 pub mod auth_api;
+pub mod health_api;
 pub mod hello_api;
 pub mod location_api;
+pub mod metrics_api;
 pub mod ping_api;
 pub mod user_api;
 pub mod task_api;
 pub mod aggregator_api;
+pub mod trash_api;
 
 pub use auth_api::init as init_auth_api;
+pub use health_api::init as init_health_api;
 pub use hello_api::init as init_hello_api;
 pub use location_api::init as init_location_api;
+pub use metrics_api::init as init_metrics_api;
 pub use ping_api::init as init_ping_api;
 pub use user_api::init as init_user_api;
 pub use task_api::init as init_task_api;
-pub use aggregator_api::init as init_aggregator_api;
\ No newline at end of file
+pub use aggregator_api::init_aggregator_api;
+pub use trash_api::init as init_trash_api;
\ No newline at end of file
@@ -1,45 +1,57 @@
 use actix_web::{
-    delete, get, post, put, web,
-    web::{Data, Json, Path},
-    HttpResponse,
+    delete, get, patch, post, put, web,
+    http::header::{IfMatch, IfNoneMatch},
+    web::{Data, Header, Json, Path},
+    HttpMessage, HttpRequest, HttpResponse,
 };
 use actix_web_grants::proc_macro::has_any_role;
 use log::warn;
-use mongodb::Client;
 use serde::Deserialize;
 use validator::Validate;
 
-use actix_web:: Responder;
-use crate::services::{aggregator_service, task_service::TaskService};
-
-
-
 use crate::{
-    models::{error_model::ApiErrorType, task_model::Task, TaskAggregate},
+    config::settings::Settings,
+    models::{
+        bulk_model::BulkTaskRequest,
+        error_model::ApiErrorType,
+        export_model::{ExportFormat, ExportOptions, ImportOptions},
+        task_model::{Task, TaskPatch},
+    },
+    repository::task_repo::TaskRepository,
     services::task_service,
+    telemetry,
 };
 
 
 pub fn init(cfg: &mut web::ServiceConfig) {
     cfg.service(create_task);
+    cfg.service(bulk_tasks);
+    cfg.service(export_tasks);
+    cfg.service(import_tasks);
+    cfg.service(task_stats);
     cfg.service(get_task);
     cfg.service(update_task);
+    cfg.service(patch_task);
     cfg.service(delete_task);
+    cfg.service(restore_task);
     cfg.service(get_all_tasks);
-    cfg.service(create_task).service(aggregate_tasks);
 }
 
 
 #[post("/tasks")]
 pub async fn create_task(
-    client: Data<Client>,
+    repo: Data<dyn TaskRepository>,
     new_task: Json<Task>,
 ) -> Result<HttpResponse, ApiErrorType> {
-
-    println!("Here");
     let is_valid = new_task.validate();
     match is_valid {
-        Ok(_) => task_service::create_task(&client, new_task).await,
+        Ok(_) => {
+            telemetry::traced(
+                "task_service.create_task",
+                task_service::create_task(repo.get_ref(), new_task),
+            )
+            .await
+        }
         Err(err) => {
             warn!("Payload validation Error on add task: {}", err);
             // Validation error.
@@ -51,50 +63,201 @@ pub async fn create_task(
     }
 }
 
-  async fn aggregate_tasks(task_service: web::Data<TaskService>) -> impl Responder { 
-    match task_service.aggregate_tasks().await {
-        Ok(aggregator_tasks)  => HttpResponse::Ok().json(aggregated_tasks),
-        Err(_) => HttpResponse::InternalServerError().json(ApiErrorType::AggregatorError),
-    }   
+#[derive(Deserialize)]
+pub struct BulkOptions {
+    pub atomic: Option<bool>,
+}
+
+// Create, update and delete tasks in one call. `atomic=true` runs them in a transaction.
+#[post("/tasks/bulk")]
+pub async fn bulk_tasks(
+    repo: Data<dyn TaskRepository>,
+    settings: Data<Settings>,
+    options: web::Query<BulkOptions>,
+    request: Json<BulkTaskRequest>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let atomic = options.atomic.unwrap_or(false);
+    telemetry::traced(
+        "task_service.bulk_tasks",
+        task_service::bulk_tasks(
+            repo.get_ref(),
+            request.into_inner(),
+            atomic,
+            &settings.bulk,
+            &settings.errors,
+        ),
+    )
+    .await
+}
+
+// Export all tasks as `format=csv` or `format=ndjson`.
+#[get("/tasks/export")]
+pub async fn export_tasks(
+    repo: Data<dyn TaskRepository>,
+    options: web::Query<ExportOptions>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "task_service.export_tasks",
+        task_service::export_tasks(repo.get_ref(), options.format),
+    )
+    .await
+}
+
+// Import tasks from a CSV or NDJSON upload, picked by `format` or the `Content-Type`.
+#[post("/tasks/import")]
+pub async fn import_tasks(
+    repo: Data<dyn TaskRepository>,
+    settings: Data<Settings>,
+    req: HttpRequest,
+    options: web::Query<ImportOptions>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiErrorType> {
+    let format = options
+        .format
+        .or_else(|| ExportFormat::from_content_type(req.content_type()));
+    match format {
+        Some(format) => {
+            telemetry::traced(
+                "task_service.import_tasks",
+                task_service::import_tasks(
+                    repo.get_ref(),
+                    &body,
+                    format,
+                    &settings.bulk,
+                    &settings.errors,
+                ),
+            )
+            .await
+        }
+        None => {
+            warn!("Unsupported task import format: {}", req.content_type());
+            Err(ApiErrorType::BadRequest)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StatsRange {
+    // `YYYY-MM-DD` dates, both included.
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+// Counts by status, tag, assignee and creation day of the tasks created in the range.
+#[get("/tasks/stats")]
+pub async fn task_stats(
+    repo: Data<dyn TaskRepository>,
+    settings: Data<Settings>,
+    range: web::Query<StatsRange>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "task_service.task_stats",
+        task_service::task_stats(repo.get_ref(), &range, &settings.stats),
+    )
+    .await
 }
 
 #[get("/tasks/{id}")]
 pub async fn get_task(
-    client: Data<Client>,
+    repo: Data<dyn TaskRepository>,
     path: Path<String>,
+    if_none_match: Option<Header<IfNoneMatch>>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    task_service::get_task_by_id(&client, path).await
+    telemetry::traced(
+        "task_service.get_task_by_id",
+        task_service::get_task_by_id(repo.get_ref(), path, if_none_match.map(Header::into_inner)),
+    )
+    .await
 }
 
 #[put("/tasks/{id}")]
 pub async fn update_task(
-    client: Data<Client>,
+    repo: Data<dyn TaskRepository>,
     path: Path<String>,
     update_task: Json<Task>,
+    if_match: Option<Header<IfMatch>>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "task_service.update_task",
+        task_service::update_task(repo.get_ref(), path, update_task, if_match.map(Header::into_inner)),
+    )
+    .await
+}
+
+#[patch("/tasks/{id}")]
+pub async fn patch_task(
+    repo: Data<dyn TaskRepository>,
+    path: Path<String>,
+    patch: Json<TaskPatch>,
+    if_match: Option<Header<IfMatch>>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    task_service::update_task(&client, path, update_task).await
+    match patch.validate() {
+        Ok(_) => {
+            let if_match = if_match.map(Header::into_inner);
+            telemetry::traced(
+                "task_service.patch_task",
+                task_service::patch_task(repo.get_ref(), path, patch.into_inner(), if_match),
+            )
+            .await
+        }
+        Err(err) => {
+            warn!("Payload validation Error on patch task: {}", err);
+            Err(ApiErrorType::ValidationError {
+                validation_error: err,
+                object: "Task".to_string(),
+            })
+        }
+    }
 }
 
 #[delete("/tasks/{id}")]
 pub async fn delete_task(
-    client: Data<Client>,
+    repo: Data<dyn TaskRepository>,
+    path: Path<String>,
+    if_match: Option<Header<IfMatch>>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "task_service.delete_task",
+        task_service::delete_task(repo.get_ref(), path, if_match.map(Header::into_inner)),
+    )
+    .await
+}
+
+#[post("/tasks/{id}/restore")]
+pub async fn restore_task(
+    repo: Data<dyn TaskRepository>,
     path: Path<String>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    task_service::delete_task(&client, path).await
+    telemetry::traced(
+        "task_service.restore_task",
+        task_service::restore_task(repo.get_ref(), path),
+    )
+    .await
 }
 
 #[derive(Deserialize)]
 pub struct Pagination {
     pub offset: Option<u64>,
     pub limit: Option<i64>,
+    // Opaque keyset cursors. Setting either one (even empty) switches to cursor mode.
+    pub after: Option<String>,
+    pub before: Option<String>,
+    // Count total results in cursor mode.
+    pub count: Option<bool>,
 }
 
 #[get("/tasks")]
 #[has_any_role("USER")] 
 pub async fn get_all_tasks(
-    client: Data<Client>,
+    repo: Data<dyn TaskRepository>,
+    settings: Data<Settings>,
+    req: HttpRequest,
     pagination: web::Query<Pagination>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    task_service::get_all_tasks(&client, &pagination.0).await
+    telemetry::traced(
+        "task_service.get_all_tasks",
+        task_service::get_all_tasks(repo.get_ref(), &req, &pagination.0, &settings.pagination),
+    )
+    .await
 }
 
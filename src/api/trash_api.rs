@@ -0,0 +1,38 @@
+use actix_web::{
+    get, web,
+    web::Data,
+    HttpRequest, HttpResponse,
+};
+use actix_web_grants::proc_macro::has_any_role;
+use serde::Deserialize;
+
+use crate::{
+    config::settings::Settings, models::error_model::ApiErrorType, repository::trash_repo::TrashRepository,
+    services::trash_service, telemetry,
+};
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_trash);
+}
+
+#[derive(Deserialize)]
+pub struct TrashPagination {
+    pub offset: Option<u64>,
+    pub limit: Option<i64>,
+}
+
+// List soft deleted tasks and users, most recently deleted first.
+#[get("/trash")]
+#[has_any_role("USER")]
+pub async fn get_trash(
+    repo: Data<dyn TrashRepository>,
+    settings: Data<Settings>,
+    req: HttpRequest,
+    pagination: web::Query<TrashPagination>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "trash_service.get_trash",
+        trash_service::get_trash(repo.get_ref(), &req, &pagination.0, &settings.pagination),
+    )
+    .await
+}
@@ -1,25 +1,36 @@
 use actix_web::{
-    delete, get, post, put, web,
-    web::{Data, Json, Path},
-    HttpResponse,
+    delete, get, patch, post, put, web,
+    http::header::{IfMatch, IfNoneMatch},
+    web::{Data, Header, Json, Path},
+    HttpMessage, HttpRequest, HttpResponse,
 };
 use actix_web_grants::proc_macro::has_any_role;
 use log::warn;
-use mongodb::Client;
 use serde::Deserialize;
 use validator::Validate;
 
 use crate::{
-    models::{error_model::ApiErrorType, user_model::User},
+    config::settings::Settings,
+    models::{
+        error_model::ApiErrorType,
+        export_model::{ExportFormat, ExportOptions, ImportOptions},
+        user_model::{User, UserPatch},
+    },
+    repository::user_repo::UserRepository,
     services::user_service,
+    telemetry,
 };
 
 // -- Configurations...
 pub fn init(cfg: &mut web::ServiceConfig) {
     cfg.service(create_user);
+    cfg.service(export_users);
+    cfg.service(import_users);
     cfg.service(get_user);
     cfg.service(update_user);
+    cfg.service(patch_user);
     cfg.service(delete_user);
+    cfg.service(restore_user);
     cfg.service(get_all_users);
 }
 
@@ -27,12 +38,18 @@ pub fn init(cfg: &mut web::ServiceConfig) {
 // Create a user
 #[post("/users")]
 pub async fn create_user(
-    client: Data<Client>,
+    repo: Data<dyn UserRepository>,
     new_user: Json<User>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let is_valid = new_user.validate();
     match is_valid {
-        Ok(_) => user_service::create_user(&client, new_user).await,
+        Ok(_) => {
+            telemetry::traced(
+                "user_service.create_user",
+                user_service::create_user(repo.get_ref(), new_user),
+            )
+            .await
+        }
         Err(err) => {
             warn!("Payload validation Error on add user: {}", err);
             // Validation error.
@@ -44,46 +61,158 @@ pub async fn create_user(
     }
 }
 
+// Export all users as `format=csv` or `format=ndjson`.
+#[get("/users/export")]
+pub async fn export_users(
+    repo: Data<dyn UserRepository>,
+    options: web::Query<ExportOptions>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "user_service.export_users",
+        user_service::export_users(repo.get_ref(), options.format),
+    )
+    .await
+}
+
+// Import users from a CSV or NDJSON upload, picked by `format` or the `Content-Type`.
+#[post("/users/import")]
+pub async fn import_users(
+    repo: Data<dyn UserRepository>,
+    settings: Data<Settings>,
+    req: HttpRequest,
+    options: web::Query<ImportOptions>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiErrorType> {
+    let format = options
+        .format
+        .or_else(|| ExportFormat::from_content_type(req.content_type()));
+    match format {
+        Some(format) => {
+            telemetry::traced(
+                "user_service.import_users",
+                user_service::import_users(
+                    repo.get_ref(),
+                    &body,
+                    format,
+                    &settings.bulk,
+                    &settings.errors,
+                ),
+            )
+            .await
+        }
+        None => {
+            warn!("Unsupported user import format: {}", req.content_type());
+            Err(ApiErrorType::BadRequest)
+        }
+    }
+}
+
 // Get user by unique user id.
 #[get("/users/{id}")]
 pub async fn get_user(
-    client: Data<Client>,
+    repo: Data<dyn UserRepository>,
     path: Path<String>,
+    if_none_match: Option<Header<IfNoneMatch>>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    user_service::get_user_by_id(&client, path).await
+    telemetry::traced(
+        "user_service.get_user_by_id",
+        user_service::get_user_by_id(repo.get_ref(), path, if_none_match.map(Header::into_inner)),
+    )
+    .await
 }
 
 // Update user by unique user id.
 #[put("/users/{id}")]
 pub async fn update_user(
-    client: Data<Client>,
+    repo: Data<dyn UserRepository>,
     path: Path<String>,
     update_user: Json<User>,
+    if_match: Option<Header<IfMatch>>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "user_service.update_user",
+        user_service::update_user(repo.get_ref(), path, update_user, if_match.map(Header::into_inner)),
+    )
+    .await
+}
+
+// Partially update user by unique user id (JSON merge patch, RFC 7396).
+#[patch("/users/{id}")]
+pub async fn patch_user(
+    repo: Data<dyn UserRepository>,
+    path: Path<String>,
+    patch: Json<UserPatch>,
+    if_match: Option<Header<IfMatch>>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    user_service::update_user(&client, path, update_user).await
+    match patch.validate() {
+        Ok(_) => {
+            let if_match = if_match.map(Header::into_inner);
+            telemetry::traced(
+                "user_service.patch_user",
+                user_service::patch_user(repo.get_ref(), path, patch.into_inner(), if_match),
+            )
+            .await
+        }
+        Err(err) => {
+            warn!("Payload validation Error on patch user: {}", err);
+            Err(ApiErrorType::ValidationError {
+                validation_error: err,
+                object: "User".to_string(),
+            })
+        }
+    }
 }
 
 // Delete user by unique user id.
 #[delete("/users/{id}")]
 pub async fn delete_user(
-    client: Data<Client>,
+    repo: Data<dyn UserRepository>,
+    path: Path<String>,
+    if_match: Option<Header<IfMatch>>,
+) -> Result<HttpResponse, ApiErrorType> {
+    telemetry::traced(
+        "user_service.delete_user",
+        user_service::delete_user(repo.get_ref(), path, if_match.map(Header::into_inner)),
+    )
+    .await
+}
+
+// Restore user from the trash by unique user id.
+#[post("/users/{id}/restore")]
+pub async fn restore_user(
+    repo: Data<dyn UserRepository>,
     path: Path<String>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    user_service::delete_user(&client, path).await
+    telemetry::traced(
+        "user_service.restore_user",
+        user_service::restore_user(repo.get_ref(), path),
+    )
+    .await
 }
 
 #[derive(Deserialize)]
 pub struct Pagination {
     pub offset: Option<u64>,
     pub limit: Option<i64>,
+    // Opaque keyset cursors. Setting either one (even empty) switches to cursor mode.
+    pub after: Option<String>,
+    pub before: Option<String>,
+    // Count total results in cursor mode.
+    pub count: Option<bool>,
 }
 
 // Get list of all users in the database and handle pagination.
 #[get("/users")]
 #[has_any_role("USER")]
 pub async fn get_all_users(
-    client: Data<Client>,
+    repo: Data<dyn UserRepository>,
+    settings: Data<Settings>,
+    req: HttpRequest,
     pagination: web::Query<Pagination>,
 ) -> Result<HttpResponse, ApiErrorType> {
-    user_service::get_all_users(&client, &pagination.0).await
+    telemetry::traced(
+        "user_service.get_all_users",
+        user_service::get_all_users(repo.get_ref(), &req, &pagination.0, &settings.pagination),
+    )
+    .await
 }
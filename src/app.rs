@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{
+    error::Error, error::InternalError, error::JsonPayloadError, http, web, HttpMessage, HttpRequest,
+};
+use actix_web::{middleware, web::Data, web::JsonConfig, App};
+use actix_web_grants::permissions::AttachPermissions;
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::{SecondsFormat, Utc};
+use log::warn;
+
+use crate::api;
+use crate::auth::claims::Claims;
+use crate::config::settings::Settings;
+use crate::config::state::AppState;
+use crate::cors::cors;
+use crate::handler::error_handler_middleware;
+use crate::metrics::HttpMetrics;
+use crate::models::error_model::ApiError;
+use crate::rate_limit::RateLimit;
+use crate::repository::auth_repo::{AuthRepository, MongoAuthRepository};
+use crate::repository::memory_repo::InMemoryRepository;
+use crate::repository::rate_limit_repo::{
+    MemoryRateLimitRepository, MongoRateLimitRepository, RateLimitRepository,
+};
+use crate::repository::task_repo::{MongoTaskRepository, TaskRepository};
+use crate::repository::trash_repo::{MongoTrashRepository, TrashRepository};
+use crate::repository::user_repo::{MongoUserRepository, UserRepository};
+use crate::request_id::{self, RequestId, RequestIds};
+use crate::services::aggregator_service::AggregatorService;
+use crate::services::health_service::{HealthChecks, MongoHealthCheck};
+use crate::telemetry::RequestTracing;
+
+// Repositories the services read and write through, shared by every worker.
+#[derive(Clone)]
+pub struct Repositories {
+    pub tasks: Arc<dyn TaskRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub auth: Arc<dyn AuthRepository>,
+    pub trash: Arc<dyn TrashRepository>,
+    // Rate limit buckets, in memory unless shared through MongoDB.
+    pub rate_limits: Arc<dyn RateLimitRepository>,
+    // Dependency checks of the readiness endpoint.
+    pub health: HealthChecks,
+}
+
+impl Repositories {
+    // Every repository on the collections of the one application state.
+    pub fn mongo(state: &AppState) -> Self {
+        let mut health = HealthChecks::default();
+        health.register(MongoHealthCheck::new(state.database.clone()));
+        let rate_limits: Arc<dyn RateLimitRepository> = match state.settings.rate_limit.store.as_str() {
+            "mongo" => Arc::new(MongoRateLimitRepository::new(state)),
+            _ => Arc::new(MemoryRateLimitRepository::new()),
+        };
+        Self {
+            tasks: Arc::new(MongoTaskRepository::new(state)),
+            users: Arc::new(MongoUserRepository::new(state)),
+            auth: Arc::new(MongoAuthRepository::new(state)),
+            trash: Arc::new(MongoTrashRepository::new(state)),
+            rate_limits,
+            health,
+        }
+    }
+
+    // Everything in process memory, for tests without MongoDB.
+    pub fn in_memory() -> Self {
+        let repo = Arc::new(InMemoryRepository::new());
+        Self {
+            tasks: repo.clone(),
+            users: repo.clone(),
+            auth: repo.clone(),
+            trash: repo,
+            rate_limits: Arc::new(MemoryRateLimitRepository::new()),
+            health: HealthChecks::default(),
+        }
+    }
+}
+
+// Build the app with every middleware and controller.
+// Shared by `main` and the integration tests so both serve the same routes.
+pub fn create_app(
+    settings: Arc<Settings>,
+    repos: Repositories,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let auth = HttpAuthentication::bearer(validator);
+    let rate_limit = RateLimit::new(&settings.rate_limit, repos.rate_limits);
+    App::new()
+        // Every error response in the `ApiError` shape, before it gets compressed
+        .wrap(error_handler_middleware::api_errors())
+        // Configure compression
+        .wrap(middleware::Compress::default())
+        // Configure app data
+        .app_data(Data::from(repos.tasks))
+        .app_data(Data::from(repos.users))
+        .app_data(Data::from(repos.auth))
+        .app_data(Data::from(repos.trash))
+        .app_data(Data::new(repos.health))
+        .app_data(Data::new(AggregatorService::new(&settings.aggregator.base_url)))
+        .app_data(Data::from(settings.clone()))
+        .app_data(JsonConfig::default().error_handler(json_error_handler))
+        // Configure un-secure auth controller under '/a' scope
+        .service(
+            web::scope("/a")
+                .wrap(rate_limit.clone())
+                .wrap(cors(&settings.cors.scope(&settings.cors.auth)))
+                .configure(api::init_auth_api),
+        )
+        // Configure secure controller with JWT authentication under '/api' scope,
+        // rate limited after authentication to know the account.
+        // Requests without a token get a 401, CORS preflights are answered before authentication.
+        .service(
+            web::scope("/api")
+                .wrap(rate_limit.clone())
+                .wrap(auth)
+                .wrap(cors(&settings.cors.scope(&settings.cors.api)))
+                .configure(api::init_user_api)
+                .configure(api::init_hello_api)
+                .configure(api::init_task_api)
+                .configure(api::init_aggregator_api)
+                .configure(api::init_trash_api),
+        )
+        // Configure the other un-secure controllers with the global CORS policy
+        .service(
+            web::scope("")
+                .wrap(rate_limit)
+                .wrap(cors(&settings.cors.global()))
+                .configure(api::init_ping_api)
+                .configure(api::init_health_api)
+                .configure(api::init_metrics_api)
+                .configure(api::init_location_api),
+        )
+        .wrap(middleware::DefaultHeaders::new().add(("X-Version", "0.3.0")))
+        .wrap(HttpMetrics)
+        .wrap(
+            middleware::Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{request_id}xi"#)
+                .custom_request_replace("request_id", |req| {
+                    req.extensions()
+                        .get::<RequestId>()
+                        .map(|id| id.0.clone())
+                        .unwrap_or_default()
+                }),
+        )
+        .wrap(RequestTracing)
+        .wrap(RequestIds)
+}
+
+fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> Error {
+    let detail = err.to_string();
+    let error = match &err {
+        JsonPayloadError::ContentType => ApiError {
+            problem_type: Some("unsupported-media-type"),
+            status: 415,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            message: "Unsupported media type".to_owned(),
+            debug_message: Some(detail),
+            sub_errors: Vec::new(),
+            request_id: request_id::current(),
+            error_id: None,
+        },
+        JsonPayloadError::Deserialize(json_err) if json_err.is_data() => ApiError {
+            problem_type: Some("unprocessable-payload"),
+            status: 422,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            message: "Unprocessable payload".to_owned(),
+            debug_message: Some(detail),
+            sub_errors: Vec::new(),
+            request_id: request_id::current(),
+            error_id: None,
+        },
+        _ => ApiError {
+            problem_type: Some("bad-request"),
+            status: 400,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            message: "Bad request. Missing parameter and/or wrong payload.".to_owned(),
+            debug_message: Some(detail),
+            sub_errors: Vec::new(),
+            request_id: request_id::current(),
+            error_id: None,
+        },
+    };
+    let status = http::StatusCode::from_u16(error.status).unwrap_or(http::StatusCode::BAD_REQUEST);
+    InternalError::from_response(err, error.into_response(status)).into()
+}
+
+async fn validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    warn!("Validating JWT auth");
+    let settings = req.app_data::<Data<Settings>>().unwrap();
+    let result = Claims::decode_jwt(credentials.token(), &settings.jwt);
+    match result {
+        Ok(claims) => {
+            req.attach(claims.permissions.clone());
+            req.extensions_mut().insert(claims);
+            Ok(req)
+        }
+        Err(e) => Err((e, req)),
+    }
+}
@@ -5,12 +5,9 @@ use jsonwebtoken::{encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
 use crate::api::auth_api::LoginResponse;
+use crate::config::settings::JwtSettings;
 use crate::models::auth_model::Auth;
 
-// JWT lifetime and Secret key.
-const JWT_EXPIRATION_HOURS: i64 = 24;
-const SECRET: &str = "secret";
-
 // Claims for JWT Body.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -24,20 +21,20 @@ pub struct Claims {
 // Kind of constructor for Rust.
 // TODO: Use Public and Private key to Signing and verifying JWT token.
 impl Claims {
-    pub fn new(sub: &String, permissions: &Vec<String>) -> Self {
+    pub fn new(sub: &String, permissions: &Vec<String>, jwt: &JwtSettings) -> Self {
         Self {
             sub: sub.to_string(),
             permissions: permissions.to_owned(),
-            iss: String::from("https://c12.io"),
-            exp: (Utc::now() + Duration::hours(JWT_EXPIRATION_HOURS)).timestamp(),
+            iss: jwt.issuer.clone(),
+            exp: (Utc::now() + Duration::hours(jwt.expiration_hours)).timestamp(),
             iat: (Utc::now()).timestamp(),
         }
     }
 
     // Create JWT token from Auth values.
-    pub fn create_jwt_token(auth: &Auth) -> Result<LoginResponse, Error> {
-        let claim = Claims::new(&auth.id, &auth.roles);
-        let encoding_key = EncodingKey::from_secret(SECRET.as_bytes());
+    pub fn create_jwt_token(auth: &Auth, jwt: &JwtSettings) -> Result<LoginResponse, Error> {
+        let claim = Claims::new(&auth.id, &auth.roles, jwt);
+        let encoding_key = EncodingKey::from_secret(jwt.secret.as_bytes());
         let jwt_token = encode(&Header::default(), &claim, &encoding_key);
 
         match jwt_token {
@@ -50,8 +47,8 @@ impl Claims {
     }
 
     // Decode JWT and validate signature
-    pub fn decode_jwt(token: &str) -> Result<Claims, Error> {
-        let decoding_key = DecodingKey::from_secret(SECRET.as_bytes());
+    pub fn decode_jwt(token: &str, jwt: &JwtSettings) -> Result<Claims, Error> {
+        let decoding_key = DecodingKey::from_secret(jwt.secret.as_bytes());
         jsonwebtoken::decode::<Claims>(token, &decoding_key, &Validation::default())
             .map(|data| data.claims)
             .map_err(ErrorUnauthorized)
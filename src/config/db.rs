@@ -1,22 +1,76 @@
-use log::error;
+use derive_more::{Display, Error};
+use log::{info, warn};
+use mongodb::bson::doc;
+use mongodb::options::{Acknowledgment, ClientOptions, ReadConcern, WriteConcern};
 use mongodb::Client;
-use std::borrow::ToOwned;
-use std::env;
+use std::time::Duration;
+
+use crate::config::settings::MongoSettings;
+
+#[derive(Debug, Display, Error)]
+pub enum DbError {
+    #[display(fmt = "MongoDB is not reachable after {} attempts: {}", attempts, source)]
+    Unreachable {
+        attempts: u32,
+        source: mongodb::error::Error,
+    },
+
+    #[display(fmt = "MongoDB error: {}", _0)]
+    Mongo(mongodb::error::Error),
+}
 
 // MongoDB initialize function.
-// Get DB connection url from environment file and connect.
-pub async fn init() -> Client {
-    let uri = match env::var("MONGO.URI") {
-        Ok(uri) => uri,
-        Err(_) => {
-            error!("Error loading env info for MongoDB connection");
-            "Error loading env variables to connect to MongoDB".to_owned()
-        }
+// Build the client options from the settings, then ping the server until it answers.
+pub async fn init(settings: &MongoSettings) -> Result<Client, DbError> {
+    let options = client_options(settings).await?;
+    let client = Client::with_options(options).map_err(DbError::Mongo)?;
+    ping_with_retries(&client, settings).await?;
+    Ok(client)
+}
+
+async fn client_options(settings: &MongoSettings) -> Result<ClientOptions, DbError> {
+    let mut options = ClientOptions::parse(&settings.uri).await.map_err(DbError::Mongo)?;
+
+    options.app_name = Some(settings.app_name.clone());
+    options.max_pool_size = Some(settings.max_pool_size);
+    options.min_pool_size = Some(settings.min_pool_size);
+    options.server_selection_timeout = Some(Duration::from_millis(settings.server_selection_timeout_ms));
+    options.connect_timeout = Some(Duration::from_millis(settings.connect_timeout_ms));
+    options.read_concern = Some(ReadConcern::custom(settings.read_concern.clone()));
+
+    // Either a number of nodes or a named concern like "majority".
+    let acknowledgment = match settings.write_concern.parse::<u32>() {
+        Ok(nodes) => Acknowledgment::from(nodes),
+        Err(_) => Acknowledgment::from(settings.write_concern.clone()),
     };
+    options.write_concern = Some(WriteConcern::builder().w(acknowledgment).build());
 
-    // panic if not able to connect to DB.
-    let client = Client::with_uri_str(uri)
-        .await
-        .expect("Error connecting to backend database");
-    client
+    Ok(options)
+}
+
+// Ping with exponential backoff, giving up after `connect_retries` attempts.
+async fn ping_with_retries(client: &Client, settings: &MongoSettings) -> Result<(), DbError> {
+    let attempts = settings.connect_retries.max(1);
+    let mut backoff = Duration::from_millis(settings.retry_backoff_ms);
+    let mut attempt = 1;
+    loop {
+        match client.database("admin").run_command(doc! {"ping": 1}, None).await {
+            Ok(_) => {
+                info!("Connected to MongoDB");
+                return Ok(());
+            }
+            Err(source) if attempt >= attempts => {
+                return Err(DbError::Unreachable { attempts, source });
+            }
+            Err(err) => {
+                warn!(
+                    "MongoDB ping {} of {} failed, retrying in {:?} : {}",
+                    attempt, attempts, backoff, err
+                );
+                actix_web::rt::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_millis(settings.max_retry_backoff_ms));
+                attempt += 1;
+            }
+        }
+    }
 }
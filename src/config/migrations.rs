@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::info;
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    error::Error,
+    options::{IndexOptions, UpdateOptions},
+    Collection, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::state::AppState;
+use crate::constants;
+
+// A versioned change to the database, applied once and recorded in `_migrations`.
+// Migrations must be safe to run again: two instances starting together may both apply one.
+#[async_trait]
+trait Migration: Send + Sync {
+    fn version(&self) -> i64;
+
+    fn name(&self) -> &'static str;
+
+    async fn up(&self, state: &AppState) -> Result<(), Error>;
+}
+
+// Every migration, in the order they are applied. Never change or remove one that shipped.
+fn migrations() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(AuthEmailIndex),
+        Box::new(ListIndexes),
+        Box::new(BackfillTaskFields),
+        Box::new(RateLimitExpiry),
+    ]
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MigrationRecord {
+    #[serde(rename = "_id")]
+    version: i64,
+    name: String,
+    applied_at: DateTime,
+}
+
+// Apply every migration not recorded yet, stopping at the first failure.
+pub async fn run(state: &AppState) -> Result<(), Error> {
+    let applied: Collection<MigrationRecord> = state
+        .database
+        .collection(constants::MONGO_MIGRATION_COLLECTION);
+    for migration in migrations() {
+        let version = migration.version();
+        if applied.count_documents(doc! {"_id": version}, None).await? > 0 {
+            continue;
+        }
+        info!("Applying migration {} - {}", version, migration.name());
+        migration.up(state).await?;
+        let record = MigrationRecord {
+            version,
+            name: migration.name().to_owned(),
+            applied_at: DateTime::now(),
+        };
+        // Another instance may have recorded it in the meantime.
+        applied
+            .update_one(
+                doc! {"_id": version},
+                doc! {"$setOnInsert": bson::to_document(&record)?},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+fn index(keys: Document, name: &str, unique: bool) -> IndexModel {
+    let options = IndexOptions::builder()
+        .name(name.to_owned())
+        .unique(unique)
+        .build();
+    IndexModel::builder().keys(keys).options(options).build()
+}
+
+// Unique emails, which also makes `check_email` an index lookup.
+struct AuthEmailIndex;
+
+#[async_trait]
+impl Migration for AuthEmailIndex {
+    fn version(&self) -> i64 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "auth_email_unique_index"
+    }
+
+    async fn up(&self, state: &AppState) -> Result<(), Error> {
+        state
+            .auth
+            .create_index(index(doc! {"email": 1}, "email_unique", true), None)
+            .await?;
+        Ok(())
+    }
+}
+
+// Live documents sorted like the list endpoints, plus task creation time for the stats.
+struct ListIndexes;
+
+#[async_trait]
+impl Migration for ListIndexes {
+    fn version(&self) -> i64 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "list_sort_indexes"
+    }
+
+    async fn up(&self, state: &AppState) -> Result<(), Error> {
+        state
+            .tasks
+            .create_indexes(
+                vec![
+                    index(doc! {"deleted_at": 1, "title": 1, "_id": 1}, "live_by_title", false),
+                    index(doc! {"deleted_at": 1, "created_at": 1}, "live_by_created_at", false),
+                ],
+                None,
+            )
+            .await?;
+        state
+            .users
+            .create_index(
+                index(doc! {"deleted_at": 1, "name": 1, "_id": 1}, "live_by_name", false),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+// Tasks stored before status and tags existed get the defaults of the model.
+// Their creation time is unknown, so they stay out of the per day statistics.
+struct BackfillTaskFields;
+
+#[async_trait]
+impl Migration for BackfillTaskFields {
+    fn version(&self) -> i64 {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "backfill_task_status_and_tags"
+    }
+
+    async fn up(&self, state: &AppState) -> Result<(), Error> {
+        let tasks = state.task_documents();
+        tasks
+            .update_many(
+                doc! {"status": {"$exists": false}},
+                doc! {"$set": {"status": "todo"}},
+                None,
+            )
+            .await?;
+        tasks
+            .update_many(
+                doc! {"tags": {"$exists": false}},
+                doc! {"$set": {"tags": []}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+// Rate limit buckets are deleted once they would be full again.
+struct RateLimitExpiry;
+
+#[async_trait]
+impl Migration for RateLimitExpiry {
+    fn version(&self) -> i64 {
+        4
+    }
+
+    fn name(&self) -> &'static str {
+        "rate_limit_ttl_index"
+    }
+
+    async fn up(&self, state: &AppState) -> Result<(), Error> {
+        let options = IndexOptions::builder()
+            .name("expires_at_ttl".to_owned())
+            .expire_after(Duration::ZERO)
+            .build();
+        let index = IndexModel::builder().keys(doc! {"expires_at": 1}).options(options).build();
+        state.rate_limits.create_index(index, None).await?;
+        Ok(())
+    }
+}
@@ -1 +1,5 @@
 pub mod db;
+pub mod migrations;
+pub mod settings;
+pub mod state;
+pub mod tls;
@@ -0,0 +1,763 @@
+use std::collections::HashMap;
+use std::env;
+
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+use config::{Config, ConfigError, Environment, File};
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::constants;
+use crate::rate_limit::IpRange;
+
+// Environment variables override settings as `APP__<SECTION>__<KEY>`, e.g. `APP__SERVER__PORT=9090`.
+const ENV_PREFIX: &str = "APP";
+const ENV_SEPARATOR: &str = "__";
+// Path of the settings file. Without it `settings.toml` or `settings.yaml` is read when present.
+const FILE_VARIABLE: &str = "APP_CONFIG";
+const DEFAULT_FILE: &str = "settings";
+const REDACTED: &str = "<redacted>";
+// Settings given as comma separated lists in the environment.
+const LIST_KEYS: &[&str] = &[
+    "cors.allowed_origins",
+    "cors.allowed_methods",
+    "cors.allowed_headers",
+    "cors.expose_headers",
+    "cors.auth.allowed_origins",
+    "cors.auth.allowed_methods",
+    "cors.auth.allowed_headers",
+    "cors.auth.expose_headers",
+    "cors.api.allowed_origins",
+    "cors.api.allowed_methods",
+    "cors.api.allowed_headers",
+    "cors.api.expose_headers",
+    "rate_limit.trusted_proxies",
+];
+
+#[derive(Debug, Display, Error)]
+pub enum SettingsError {
+    #[display(fmt = "Could not load the configuration: {}", _0)]
+    Load(ConfigError),
+
+    #[display(fmt = "Invalid configuration: {}", "errors.join(\"; \")")]
+    Invalid { errors: Vec<String> },
+}
+
+// Every setting of the app, built once at startup.
+// Defaults come from `constants.rs`, then the settings file, then the environment.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct Settings {
+    #[validate(nested)]
+    pub server: ServerSettings,
+    #[validate(nested)]
+    pub tls: TlsSettings,
+    #[validate(nested)]
+    pub mongo: MongoSettings,
+    #[validate(nested)]
+    pub jwt: JwtSettings,
+    #[validate(nested)]
+    pub cors: CorsSettings,
+    #[validate(nested)]
+    pub log: LogSettings,
+    #[validate(nested)]
+    pub otel: OtelSettings,
+    #[validate(nested)]
+    pub trash: TrashSettings,
+    #[validate(nested)]
+    pub shutdown: ShutdownSettings,
+    #[validate(nested)]
+    pub rate_limit: RateLimitSettings,
+    #[validate(nested)]
+    pub errors: ErrorSettings,
+    #[validate(nested)]
+    pub pagination: PaginationSettings,
+    #[validate(nested)]
+    pub bulk: BulkSettings,
+    #[validate(nested)]
+    pub stats: StatsSettings,
+    #[validate(nested)]
+    pub health: HealthSettings,
+    #[validate(nested)]
+    pub aggregator: AggregatorSettings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct ServerSettings {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub host: String,
+    #[validate(range(min = 1, message = "must be a port number"))]
+    pub port: u16,
+}
+
+// TLS termination, HTTP/2 is offered with it. PEM files, the certificate reloads on SIGHUP.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_tls", skip_on_field_errors = false))]
+pub struct TlsSettings {
+    pub enabled: bool,
+    pub cert_path: String,
+    pub key_path: String,
+    // CAs trusted for client certificates, callers are not verified without it.
+    pub client_ca_path: Option<String>,
+    // Whether callers without a client certificate are rejected, once a CA is set.
+    pub client_cert_required: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_mongo_limits", skip_on_field_errors = false))]
+pub struct MongoSettings {
+    // Required, `APP__MONGO__URI` or the settings file.
+    #[validate(custom(function = "validate_mongo_uri"))]
+    pub uri: String,
+    #[validate(length(min = 1, max = 63, message = "must be 1 to 63 characters"))]
+    pub database: String,
+    pub task_collection: String,
+    pub user_collection: String,
+    pub auth_collection: String,
+    pub rate_limit_collection: String,
+    pub app_name: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub max_pool_size: u32,
+    pub min_pool_size: u32,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub server_selection_timeout_ms: u64,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub connect_timeout_ms: u64,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub read_concern: String,
+    // Either a number of nodes or a named concern like "majority".
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub write_concern: String,
+    // Startup pings, the backoff doubles after every failure up to the maximum.
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub connect_retries: u32,
+    pub retry_backoff_ms: u64,
+    pub max_retry_backoff_ms: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct JwtSettings {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub secret: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub expiration_hours: i64,
+    pub issuer: String,
+}
+
+// CORS policy of every route, with overrides for the `/a` and `/api` scopes.
+// Origins are exact (`https://app.c12.io`), subdomain patterns (`https://*.c12.io`) or `*`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: Option<usize>,
+    pub auth: CorsOverride,
+    pub api: CorsOverride,
+}
+
+// Parts of the policy a scope changes, the others come from the global policy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CorsOverride {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub expose_headers: Option<Vec<String>>,
+    pub allow_credentials: Option<bool>,
+    pub max_age_secs: Option<usize>,
+}
+
+// The CORS policy of one scope, with the overrides applied.
+#[derive(Clone, Debug)]
+pub struct CorsPolicy {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct LogSettings {
+    // Log4rs file, `log4rs-json.yml` logs JSON records.
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub config: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct OtelSettings {
+    // One of `otlp`, `stdout` or `none`.
+    #[validate(custom(function = "validate_exporter"))]
+    pub exporter: String,
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct TrashSettings {
+    // Soft deleted items older than the retention period are purged on every interval.
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub retention_days: u64,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub purge_interval_secs: u64,
+}
+
+// On SIGTERM or SIGINT the app turns unready, waits `drain_delay_secs` for load balancers
+// to notice, then gives requests and background jobs `grace_period_secs` to finish.
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct ShutdownSettings {
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub grace_period_secs: u64,
+    pub drain_delay_secs: u64,
+}
+
+// Token buckets per route pattern, refilled with `requests` every `period_secs`.
+// Rules are set in the settings file, a request takes a token from every rule it matches.
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    // `memory`, or `mongo` to share the buckets between replicas.
+    #[validate(custom(function = "validate_rate_limit_store"))]
+    pub store: String,
+    // Peers whose `X-Forwarded-For` gives the client address, as addresses or CIDR ranges.
+    #[validate(custom(function = "validate_trusted_proxies"))]
+    pub trusted_proxies: Vec<String>,
+    #[validate(nested)]
+    pub rules: Vec<RateLimitRule>,
+}
+
+// Format of error bodies when the client does not ask for one with `Accept`.
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct ErrorSettings {
+    // `api` for the ApiError shape, `problem` for RFC 7807 `application/problem+json`.
+    #[validate(custom(function = "validate_error_format"))]
+    pub format: String,
+    // Prefix of the problem `type` URIs.
+    #[validate(url(message = "must be a URL"))]
+    pub type_base_uri: String,
+    // Send debug messages to clients, for development. Internal errors are logged either way.
+    pub expose_details: bool,
+}
+
+// Offset and limit of list requests that do not send them, and the largest limit allowed.
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_pagination_limits", skip_on_field_errors = false))]
+pub struct PaginationSettings {
+    pub default_offset: u64,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub default_limit: i64,
+    // Larger limits are rejected with a bad request.
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub max_limit: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct BulkSettings {
+    // Operations of one bulk request, and rows of one import.
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub max_operations: usize,
+}
+
+// Task statistics cover the last `default_range_days` days unless asked otherwise.
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_stats_range", skip_on_field_errors = false))]
+pub struct StatsSettings {
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub default_range_days: u64,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub max_range_days: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct HealthSettings {
+    // Every readiness check is bounded by this timeout.
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub check_timeout_ms: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+#[serde(default)]
+pub struct AggregatorSettings {
+    // `/data1` and `/data2` are fetched from this URL.
+    #[validate(url(message = "must be a URL"))]
+    pub base_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
+pub struct RateLimitRule {
+    // Route pattern like `/api/tasks/{id}`, or a prefix of patterns ending with `*`.
+    #[validate(custom(function = "validate_route"))]
+    pub route: String,
+    // Every method when empty.
+    #[serde(default)]
+    #[validate(custom(function = "validate_methods"))]
+    pub methods: Vec<String>,
+    // `ip`, or `account` for the subject of the JWT, the IP when there is none.
+    #[validate(custom(function = "validate_rate_limit_key"))]
+    pub key: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub requests: u32,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub period_secs: u64,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: constants::SERVER_HOST.to_owned(),
+            port: constants::SERVER_PORT,
+        }
+    }
+}
+
+impl Default for MongoSettings {
+    fn default() -> Self {
+        Self {
+            uri: String::new(),
+            database: constants::MONGO_DATABASE.to_owned(),
+            task_collection: constants::MONGO_TASK_COLLECTION.to_owned(),
+            user_collection: constants::MONGO_USER_COLLECTION.to_owned(),
+            auth_collection: constants::MONGO_AUTH_COLLECTION.to_owned(),
+            rate_limit_collection: constants::MONGO_RATE_LIMIT_COLLECTION.to_owned(),
+            app_name: constants::MONGO_APP_NAME.to_owned(),
+            max_pool_size: constants::MONGO_MAX_POOL_SIZE,
+            min_pool_size: constants::MONGO_MIN_POOL_SIZE,
+            server_selection_timeout_ms: constants::MONGO_SERVER_SELECTION_TIMEOUT_MS,
+            connect_timeout_ms: constants::MONGO_CONNECT_TIMEOUT_MS,
+            read_concern: constants::MONGO_READ_CONCERN.to_owned(),
+            write_concern: constants::MONGO_WRITE_CONCERN.to_owned(),
+            connect_retries: constants::MONGO_CONNECT_RETRIES,
+            retry_backoff_ms: constants::MONGO_RETRY_BACKOFF_MS,
+            max_retry_backoff_ms: constants::MONGO_MAX_RETRY_BACKOFF_MS,
+        }
+    }
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            secret: constants::JWT_SECRET.to_owned(),
+            expiration_hours: constants::JWT_EXPIRATION_HOURS,
+            issuer: constants::JWT_ISSUER.to_owned(),
+        }
+    }
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            allowed_origins: strings(constants::CORS_ALLOWED_ORIGINS),
+            allowed_methods: strings(constants::CORS_ALLOWED_METHODS),
+            allowed_headers: strings(constants::CORS_ALLOWED_HEADERS),
+            expose_headers: strings(constants::CORS_EXPOSE_HEADERS),
+            allow_credentials: false,
+            max_age_secs: Some(constants::CORS_MAX_AGE_SECS),
+            auth: CorsOverride::default(),
+            api: CorsOverride::default(),
+        }
+    }
+}
+
+impl CorsSettings {
+    // Policy of the routes outside of the `/a` and `/api` scopes.
+    pub fn global(&self) -> CorsPolicy {
+        self.scope(&CorsOverride::default())
+    }
+
+    pub fn scope(&self, scope: &CorsOverride) -> CorsPolicy {
+        let or = |value: &Option<Vec<String>>, global: &Vec<String>| value.as_ref().unwrap_or(global).clone();
+        CorsPolicy {
+            allowed_origins: or(&scope.allowed_origins, &self.allowed_origins),
+            allowed_methods: or(&scope.allowed_methods, &self.allowed_methods),
+            allowed_headers: or(&scope.allowed_headers, &self.allowed_headers),
+            expose_headers: or(&scope.expose_headers, &self.expose_headers),
+            allow_credentials: scope.allow_credentials.unwrap_or(self.allow_credentials),
+            max_age_secs: scope.max_age_secs.or(self.max_age_secs),
+        }
+    }
+}
+
+// Every policy is checked as it is applied, so an override can not sneak in a bad combination.
+impl Validate for CorsSettings {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (scope, policy) in [
+            ("global", self.global()),
+            ("auth", self.scope(&self.auth)),
+            ("api", self.scope(&self.api)),
+        ] {
+            for error in policy.problems() {
+                errors.add(scope, error);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl CorsPolicy {
+    fn problems(&self) -> Vec<ValidationError> {
+        let mut problems = Vec::new();
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && self.allowed_origins.len() > 1 {
+            problems.push(rule("cors_origin", "* must be the only allowed origin"));
+        }
+        if any_origin && self.allow_credentials {
+            problems.push(rule("cors_credentials", "credentials can not be allowed for any origin (*)"));
+        }
+        if let Some(origin) = self.allowed_origins.iter().find(|origin| !is_origin_pattern(origin)) {
+            problems.push(message("cors_origin", format!("{} is not an origin or a *.domain pattern", origin)));
+        }
+        if let Some(method) = self
+            .allowed_methods
+            .iter()
+            .find(|method| Method::from_bytes(method.as_bytes()).is_err())
+        {
+            problems.push(message("cors_method", format!("{} is not an HTTP method", method)));
+        }
+        if let Some(header) = self
+            .allowed_headers
+            .iter()
+            .chain(&self.expose_headers)
+            .find(|header| HeaderName::from_bytes(header.as_bytes()).is_err())
+        {
+            problems.push(message("cors_header", format!("{} is not a header name", header)));
+        }
+        problems
+    }
+}
+
+// `*`, or a scheme and a host with an optional port, whose first label may be `*`.
+fn is_origin_pattern(origin: &str) -> bool {
+    if origin == "*" {
+        return true;
+    }
+    let Some((scheme, host)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = host.strip_prefix("*.").unwrap_or(host);
+    matches!(scheme, "http" | "https")
+        && !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self { config: constants::LOG_CONFIG.to_owned() }
+    }
+}
+
+impl Default for OtelSettings {
+    fn default() -> Self {
+        Self {
+            exporter: constants::OTEL_EXPORTER.to_owned(),
+            endpoint: constants::OTEL_ENDPOINT.to_owned(),
+            service_name: constants::OTEL_SERVICE_NAME.to_owned(),
+        }
+    }
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self {
+            retention_days: constants::DEFAULT_TRASH_RETENTION_DAYS,
+            purge_interval_secs: constants::TRASH_PURGE_INTERVAL_SECS,
+        }
+    }
+}
+
+impl Default for ShutdownSettings {
+    fn default() -> Self {
+        Self {
+            grace_period_secs: constants::SHUTDOWN_GRACE_PERIOD_SECS,
+            drain_delay_secs: constants::SHUTDOWN_DRAIN_DELAY_SECS,
+        }
+    }
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        let rule = |route: &str, methods: &[&str], key: &str, requests, period_secs| RateLimitRule {
+            route: route.to_owned(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            key: key.to_owned(),
+            requests,
+            period_secs,
+        };
+        Self {
+            enabled: true,
+            store: constants::RATE_LIMIT_STORE.to_owned(),
+            trusted_proxies: constants::RATE_LIMIT_TRUSTED_PROXIES.iter().map(|p| p.to_string()).collect(),
+            // Unauthenticated routes that hash passwords or call out, then a ceiling per account.
+            rules: vec![
+                rule("/a/register", &["POST"], "ip", 5, 60),
+                rule("/a/login", &["POST"], "ip", 10, 60),
+                rule("/a/forgot-password", &["POST"], "ip", 5, 60),
+                rule("/locations", &[], "ip", 30, 60),
+                rule("/api/*", &[], "account", 600, 60),
+            ],
+        }
+    }
+}
+
+impl Default for ErrorSettings {
+    fn default() -> Self {
+        Self {
+            format: constants::ERROR_FORMAT.to_owned(),
+            type_base_uri: constants::ERROR_TYPE_BASE_URI.to_owned(),
+            expose_details: constants::ERROR_EXPOSE_DETAILS,
+        }
+    }
+}
+
+impl Default for PaginationSettings {
+    fn default() -> Self {
+        Self {
+            default_offset: constants::DEFAULT_OFFSET_SIZE,
+            default_limit: constants::DEFAULT_LIMIT_SIZE,
+            max_limit: constants::MAX_LIMIT_SIZE,
+        }
+    }
+}
+
+impl Default for BulkSettings {
+    fn default() -> Self {
+        Self { max_operations: constants::MAX_BULK_OPERATIONS }
+    }
+}
+
+impl Default for StatsSettings {
+    fn default() -> Self {
+        Self {
+            default_range_days: constants::DEFAULT_STATS_RANGE_DAYS,
+            max_range_days: constants::MAX_STATS_RANGE_DAYS,
+        }
+    }
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self { check_timeout_ms: constants::HEALTH_CHECK_TIMEOUT_MS }
+    }
+}
+
+impl Default for AggregatorSettings {
+    fn default() -> Self {
+        Self { base_url: constants::AGGREGATOR_BASE_URL.to_owned() }
+    }
+}
+
+impl Settings {
+    // Settings of the process: the file named by `APP_CONFIG` and the `APP__*` variables.
+    pub fn load() -> Result<Self, SettingsError> {
+        Self::build(env::var(FILE_VARIABLE).ok().as_deref(), None)
+    }
+
+    // Layer the defaults, the file and the environment, then validate the result.
+    // `vars` replaces the process environment when given.
+    pub fn build(file: Option<&str>, vars: Option<HashMap<String, String>>) -> Result<Self, SettingsError> {
+        let file = match file {
+            Some(path) => File::with_name(path).required(true),
+            None => File::with_name(DEFAULT_FILE).required(false),
+        };
+        let environment = LIST_KEYS.iter().fold(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator(ENV_SEPARATOR)
+                .separator(ENV_SEPARATOR)
+                .list_separator(","),
+            |environment, key| environment.with_list_parse_key(key),
+        )
+            .try_parsing(true)
+            .source(vars);
+        let settings: Settings = Config::builder()
+            .add_source(Config::try_from(&Settings::default()).map_err(SettingsError::Load)?)
+            .add_source(file)
+            .add_source(environment)
+            .build()
+            .and_then(Config::try_deserialize)
+            .map_err(SettingsError::Load)?;
+
+        settings.validate().map_err(|errors| {
+            let mut messages = Vec::new();
+            collect_errors("", &errors, &mut messages);
+            messages.sort();
+            SettingsError::Invalid { errors: messages }
+        })?;
+        Ok(settings)
+    }
+
+    // A copy safe to print, without the JWT secret or the MongoDB password.
+    pub fn redacted(&self) -> Settings {
+        let mut settings = self.clone();
+        settings.jwt.secret = REDACTED.to_owned();
+        settings.mongo.uri = redact_uri_password(&settings.mongo.uri);
+        settings
+    }
+}
+
+// `section.field: message` for every failed rule.
+fn collect_errors(path: &str, errors: &ValidationErrors, messages: &mut Vec<String>) {
+    for (field, kind) in errors.errors() {
+        // Rules on a whole section are reported under `__all__`.
+        let name = match (*field, path.is_empty()) {
+            ("__all__", _) => path.to_owned(),
+            (_, true) => field.to_string(),
+            (_, false) => format!("{}.{}", path, field),
+        };
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                for error in field_errors {
+                    let message = error.message.as_deref().unwrap_or(&error.code);
+                    messages.push(format!("{}: {}", name, message));
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => collect_errors(&name, nested, messages),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_errors(&format!("{}[{}]", name, index), nested, messages);
+                }
+            }
+        }
+    }
+}
+
+fn rule(code: &'static str, text: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(text.into())
+}
+
+fn message(code: &'static str, text: String) -> ValidationError {
+    ValidationError::new(code).with_message(text.into())
+}
+
+fn validate_mongo_uri(uri: &str) -> Result<(), ValidationError> {
+    if uri.is_empty() {
+        Err(rule("required", "is required"))
+    } else if uri.starts_with("mongodb://") || uri.starts_with("mongodb+srv://") {
+        Ok(())
+    } else {
+        Err(rule("mongo_uri", "must start with mongodb:// or mongodb+srv://"))
+    }
+}
+
+fn validate_tls(tls: &TlsSettings) -> Result<(), ValidationError> {
+    if tls.enabled && (tls.cert_path.is_empty() || tls.key_path.is_empty()) {
+        return Err(rule("tls_files", "cert_path and key_path are required when TLS is enabled"));
+    }
+    if tls.client_cert_required && tls.client_ca_path.is_none() {
+        return Err(rule("tls_client_ca", "client_ca_path is required to require client certificates"));
+    }
+    Ok(())
+}
+
+fn validate_rate_limit_store(store: &str) -> Result<(), ValidationError> {
+    match store {
+        "memory" | "mongo" => Ok(()),
+        _ => Err(rule("rate_limit_store", "must be one of memory or mongo")),
+    }
+}
+
+fn validate_error_format(format: &str) -> Result<(), ValidationError> {
+    match format {
+        "api" | "problem" => Ok(()),
+        _ => Err(rule("error_format", "must be one of api or problem")),
+    }
+}
+
+fn validate_trusted_proxies(proxies: &[String]) -> Result<(), ValidationError> {
+    match proxies.iter().find(|proxy| proxy.parse::<IpRange>().is_err()) {
+        Some(proxy) => Err(message("trusted_proxy", format!("{} is not an address or a CIDR range", proxy))),
+        None => Ok(()),
+    }
+}
+
+fn validate_route(route: &str) -> Result<(), ValidationError> {
+    if !route.starts_with('/') || route.trim_end_matches('*').contains('*') {
+        return Err(rule("route", "must be a route pattern starting with /, * only at the end"));
+    }
+    Ok(())
+}
+
+fn validate_methods(methods: &[String]) -> Result<(), ValidationError> {
+    match methods.iter().find(|method| Method::from_bytes(method.as_bytes()).is_err()) {
+        Some(method) => Err(message("method", format!("{} is not an HTTP method", method))),
+        None => Ok(()),
+    }
+}
+
+fn validate_rate_limit_key(key: &str) -> Result<(), ValidationError> {
+    match key {
+        "ip" | "account" => Ok(()),
+        _ => Err(rule("rate_limit_key", "must be one of ip or account")),
+    }
+}
+
+fn validate_mongo_limits(mongo: &MongoSettings) -> Result<(), ValidationError> {
+    if mongo.min_pool_size > mongo.max_pool_size {
+        return Err(rule("pool_size", "min_pool_size must not exceed max_pool_size"));
+    }
+    if mongo.retry_backoff_ms > mongo.max_retry_backoff_ms {
+        return Err(rule("retry_backoff", "retry_backoff_ms must not exceed max_retry_backoff_ms"));
+    }
+    Ok(())
+}
+
+fn validate_pagination_limits(pagination: &PaginationSettings) -> Result<(), ValidationError> {
+    if pagination.default_limit > pagination.max_limit {
+        return Err(rule("pagination_limits", "default_limit must not exceed max_limit"));
+    }
+    Ok(())
+}
+
+fn validate_stats_range(stats: &StatsSettings) -> Result<(), ValidationError> {
+    if stats.default_range_days > stats.max_range_days {
+        return Err(rule("stats_range", "default_range_days must not exceed max_range_days"));
+    }
+    Ok(())
+}
+
+fn validate_exporter(exporter: &str) -> Result<(), ValidationError> {
+    match exporter {
+        "otlp" | "stdout" | "none" => Ok(()),
+        _ => Err(rule("exporter", "must be one of otlp, stdout or none")),
+    }
+}
+
+// `mongodb://user:<redacted>@host`, the URI unchanged when it has no password.
+fn redact_uri_password(uri: &str) -> String {
+    let Some((scheme, rest)) = uri.split_once("://") else {
+        return uri.to_owned();
+    };
+    let Some((credentials, hosts)) = rest.split_once('@') else {
+        return uri.to_owned();
+    };
+    match credentials.split_once(':') {
+        Some((user, _)) => format!("{}://{}:{}@{}", scheme, user, REDACTED, hosts),
+        None => uri.to_owned(),
+    }
+}
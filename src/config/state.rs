@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use mongodb::{bson::Document, Client, Collection, Database};
+
+use crate::config::settings::Settings;
+use crate::models::{auth_model::Auth, task_model::Task, user_model::User};
+
+// Application state built once at startup.
+// Every Mongo repository takes its collections from here, all on the same database.
+// Holds the settings the app was started with.
+#[derive(Clone)]
+pub struct AppState {
+    pub client: Client,
+    pub database: Database,
+    pub tasks: Collection<Task>,
+    pub users: Collection<User>,
+    pub auth: Collection<Auth>,
+    // Token buckets of the rate limiter, when they are shared through MongoDB.
+    pub rate_limits: Collection<Document>,
+    pub settings: Arc<Settings>,
+}
+
+impl AppState {
+    pub fn new(client: Client, settings: Arc<Settings>) -> Self {
+        let database = client.database(&settings.mongo.database);
+        Self {
+            tasks: database.collection(&settings.mongo.task_collection),
+            users: database.collection(&settings.mongo.user_collection),
+            auth: database.collection(&settings.mongo.auth_collection),
+            rate_limits: database.collection(&settings.mongo.rate_limit_collection),
+            database,
+            client,
+            settings,
+        }
+    }
+
+    // Untyped handles on the task and user collections, for cross collection queries.
+    pub fn task_documents(&self) -> Collection<Document> {
+        self.tasks.clone_with_type()
+    }
+
+    pub fn user_documents(&self) -> Collection<Document> {
+        self.users.clone_with_type()
+    }
+}
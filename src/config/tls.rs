@@ -0,0 +1,129 @@
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use actix_web::rt::signal::unix::{signal, SignalKind};
+use derive_more::{Display, Error};
+use log::{error, info};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::{self, PemObject};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, VerifierBuilderError, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig};
+
+use crate::config::settings::TlsSettings;
+
+#[derive(Debug, Display, Error)]
+pub enum TlsError {
+    #[display(fmt = "Could not read {}: {}", path, source)]
+    Pem { path: String, source: pem::Error },
+
+    #[display(fmt = "No certificate found in {}", path)]
+    NoCertificate { path: String },
+
+    #[display(fmt = "Invalid certificate or key: {}", _0)]
+    Rustls(rustls::Error),
+
+    #[display(fmt = "Invalid client CA: {}", _0)]
+    ClientCa(VerifierBuilderError),
+}
+
+// Server certificate, read again from disk by `reload`.
+// Handshakes in flight keep the certificate they started with.
+#[derive(Debug)]
+pub struct CertResolver {
+    cert_path: String,
+    key_path: String,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    fn new(settings: &TlsSettings, provider: Arc<CryptoProvider>) -> Result<Self, TlsError> {
+        let current = certified_key(&settings.cert_path, &settings.key_path, &provider)?;
+        Ok(Self {
+            cert_path: settings.cert_path.clone(),
+            key_path: settings.key_path.clone(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+        })
+    }
+
+    // Swap in the files on disk, the current certificate stays when they are invalid.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let key = certified_key(&self.cert_path, &self.key_path, &self.provider)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        Ok(())
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+// Rustls config of the server, with client certificates verified when a CA is set.
+// The resolver is returned to reload the certificate later.
+pub fn server_config(settings: &TlsSettings) -> Result<(ServerConfig, Arc<CertResolver>), TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let resolver = Arc::new(CertResolver::new(settings, provider.clone())?);
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(TlsError::Rustls)?;
+    let builder = match &settings.client_ca_path {
+        None => builder.with_no_client_auth(),
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(path)? {
+                roots.add(cert).map_err(TlsError::Rustls)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if settings.client_cert_required {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            builder.with_client_cert_verifier(verifier.build().map_err(TlsError::ClientCa)?)
+        }
+    };
+    Ok((builder.with_cert_resolver(resolver.clone()), resolver))
+}
+
+// Reload the certificate on every SIGHUP, without restarting the server.
+pub fn reload_on_sighup(resolver: Arc<CertResolver>) -> io::Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    actix_web::rt::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match resolver.reload() {
+                Ok(()) => info!("TLS certificate reloaded"),
+                Err(err) => error!("Error reloading TLS certificate, keeping the current one : {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn certified_key(cert_path: &str, key_path: &str, provider: &CryptoProvider) -> Result<CertifiedKey, TlsError> {
+    let certs = read_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|source| TlsError::Pem {
+        path: key_path.to_owned(),
+        source,
+    })?;
+    CertifiedKey::from_der(certs, key, provider).map_err(TlsError::Rustls)
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|source| TlsError::Pem { path: path.to_owned(), source })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate { path: path.to_owned() });
+    }
+    Ok(certs)
+}
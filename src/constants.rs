@@ -1,9 +1,100 @@
-// MongoDB constants.
+// Defaults of `Settings`, see `config::settings` for how they are overridden.
+
+// Server defaults.
+pub const SERVER_HOST: &str = "127.0.0.1";
+pub const SERVER_PORT: u16 = 8080;
+
+// Log4rs configuration file.
+pub const LOG_CONFIG: &str = "log4rs.yml";
+
+// JWT defaults, the secret must be overridden outside of local development.
+pub const JWT_SECRET: &str = "secret";
+pub const JWT_EXPIRATION_HOURS: i64 = 24;
+pub const JWT_ISSUER: &str = "https://c12.io";
+
+// CORS policy of every route, scopes may override any part of it.
+pub const CORS_ALLOWED_ORIGINS: &[&str] = &["http://127.0.0.1:8080", "http://localhost:8080"];
+pub const CORS_ALLOWED_METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE", "PATCH"];
+pub const CORS_ALLOWED_HEADERS: &[&str] =
+    &["authorization", "accept", "content-type", "if-match", "if-none-match", "x-request-id"];
+pub const CORS_EXPOSE_HEADERS: &[&str] = &[
+    "etag",
+    "x-request-id",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+    "ratelimit-policy",
+    "retry-after",
+];
+pub const CORS_MAX_AGE_SECS: usize = 3600;
+
+// MongoDB constants. The URI has no default, a deploy must name its database server.
 pub const MONGO_DATABASE: &str = "rustDB";
 pub const MONGO_USER_COLLECTION: &str = "user";
 pub const MONGO_AUTH_COLLECTION: &str = "auth";
 pub const MONGO_TASK_COLLECTION: &str = "task";
+pub const MONGO_MIGRATION_COLLECTION: &str = "_migrations";
+pub const MONGO_RATE_LIMIT_COLLECTION: &str = "rate_limit";
+
+// MongoDB connection defaults.
+pub const MONGO_APP_NAME: &str = "actix-api";
+pub const MONGO_MAX_POOL_SIZE: u32 = 10;
+pub const MONGO_MIN_POOL_SIZE: u32 = 0;
+pub const MONGO_SERVER_SELECTION_TIMEOUT_MS: u64 = 5_000;
+pub const MONGO_CONNECT_TIMEOUT_MS: u64 = 5_000;
+pub const MONGO_READ_CONCERN: &str = "majority";
+pub const MONGO_WRITE_CONCERN: &str = "majority";
+// Startup pings, the backoff doubles after every failure up to the maximum.
+pub const MONGO_CONNECT_RETRIES: u32 = 5;
+pub const MONGO_RETRY_BACKOFF_MS: u64 = 500;
+pub const MONGO_MAX_RETRY_BACKOFF_MS: u64 = 8_000;
+
+// Health defaults, every readiness check is bounded by the timeout.
+pub const HEALTH_CHECK_TIMEOUT_MS: u64 = 2_000;
+
+// Upstream of the aggregate endpoint.
+pub const AGGREGATOR_BASE_URL: &str = "http://api.example.com";
 
-// Pagination configuration.
+// Request correlation, clients may send their own id.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+// Tracing defaults. The exporter is one of `otlp`, `stdout` or `none`.
+pub const OTEL_SERVICE_NAME: &str = "actix-api";
+pub const OTEL_EXPORTER: &str = "none";
+pub const OTEL_ENDPOINT: &str = "http://localhost:4318/v1/traces";
+
+// Pagination defaults.
 pub const DEFAULT_OFFSET_SIZE: u64 = 0;
 pub const DEFAULT_LIMIT_SIZE: i64 = 20;
+pub const MAX_LIMIT_SIZE: i64 = 100;
+
+// Bulk operations defaults, also the row limit of imports.
+pub const MAX_BULK_OPERATIONS: usize = 1000;
+
+// Task statistics defaults.
+// Ranges default to the last 30 days and can span at most a year.
+pub const DEFAULT_STATS_RANGE_DAYS: u64 = 30;
+pub const MAX_STATS_RANGE_DAYS: u64 = 366;
+
+// Trash configuration.
+// Soft deleted items older than the retention period are purged on every interval.
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+pub const TRASH_PURGE_INTERVAL_SECS: u64 = 60 * 60;
+
+// Shutdown configuration.
+// Time given to in-flight requests and background jobs, and the wait before the listener closes.
+pub const SHUTDOWN_GRACE_PERIOD_SECS: u64 = 30;
+pub const SHUTDOWN_DRAIN_DELAY_SECS: u64 = 0;
+
+// Rate limiting defaults. Buckets live in `memory` or in `mongo` to share them between replicas.
+// Only these peers are believed when they send `X-Forwarded-For`.
+pub const RATE_LIMIT_STORE: &str = "memory";
+pub const RATE_LIMIT_TRUSTED_PROXIES: &[&str] = &["127.0.0.1", "::1"];
+
+// Error responses. `api` keeps the ApiError shape, `problem` serves RFC 7807 documents.
+// Problem types are `<base>/<slug>`, clients asking for problem+json always get them.
+pub const ERROR_FORMAT: &str = "api";
+pub const ERROR_TYPE_BASE_URI: &str = "https://c12.io/problems";
+// Debug messages can name internals, like the MongoDB error of an internal error.
+pub const ERROR_EXPOSE_DETAILS: bool = false;
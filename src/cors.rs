@@ -0,0 +1,48 @@
+use actix_cors::Cors;
+
+use crate::config::settings::CorsPolicy;
+
+// Middleware applying a policy checked by `Settings`, the builder can not fail on it.
+pub fn cors(policy: &CorsPolicy) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(policy.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(policy.allowed_headers.iter().map(String::as_str))
+        .expose_headers(policy.expose_headers.iter().map(String::as_str))
+        .max_age(policy.max_age_secs);
+    if policy.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    if policy.allowed_origins.iter().any(|origin| origin == "*") {
+        return cors.allow_any_origin().send_wildcard();
+    }
+    for origin in policy.allowed_origins.iter().filter(|origin| !origin.contains('*')) {
+        cors = cors.allowed_origin(origin);
+    }
+    // Subdomain patterns are matched on every request, the allowed origin is echoed back.
+    let patterns: Vec<String> = policy
+        .allowed_origins
+        .iter()
+        .filter(|origin| origin.contains('*'))
+        .cloned()
+        .collect();
+    if !patterns.is_empty() {
+        cors = cors.allowed_origin_fn(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|pattern| matches_pattern(pattern, origin)))
+        });
+    }
+    cors
+}
+
+// `https://*.c12.io` matches `https://app.c12.io` and `https://a.b.c12.io`, not `https://c12.io`.
+fn matches_pattern(pattern: &str, origin: &str) -> bool {
+    let Some((prefix, suffix)) = pattern.split_once('*') else {
+        return pattern == origin;
+    };
+    origin.len() > prefix.len() + suffix.len()
+        && origin.starts_with(prefix)
+        && origin.ends_with(suffix)
+        && !origin[prefix.len()..origin.len() - suffix.len()].contains(['/', ':'])
+}
@@ -1 +1,92 @@
-// TODO: Implement Error handler for JWT errors and flow standard error structure.
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::{ErrorHandlerResponse, ErrorHandlers};
+use actix_web::web::Data;
+use actix_web::Result;
+
+use crate::config::settings::{ErrorSettings, Settings};
+use crate::models::error_model::{ApiError, ProblemDetails};
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+// Middleware giving every error response the `ApiError` shape, or RFC 7807 problem details
+// when the client accepts `application/problem+json` or `errors.format` is `problem`.
+// Handlers attach their `ApiError`, this also covers the bodies of actix and its extensions:
+// bearer authentication, grant denials, unknown routes, unsupported methods and extractors.
+pub fn api_errors<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().default_handler(rewrite)
+}
+
+// Status and headers are kept, only the body is replaced.
+// Debug messages are dropped unless `errors.expose_details` is set.
+fn rewrite<B>(mut res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
+    let errors = res
+        .request()
+        .app_data::<Data<Settings>>()
+        .map(|settings| settings.errors.clone())
+        .unwrap_or_default();
+    let attached = res.response_mut().extensions_mut().remove::<ApiError>();
+    let problem = wants_problem(&res, &errors);
+    let mut api_error = match attached {
+        Some(api_error) => api_error,
+        // Some other JSON document, left as it is.
+        None if is_json(&res) => return Ok(ErrorHandlerResponse::Response(res.map_into_left_body())),
+        None => {
+            let detail = res.response().error().map(|err| err.to_string());
+            ApiError::from_status(res.status(), detail)
+        }
+    };
+    if !errors.expose_details {
+        api_error.redact();
+    }
+    let (body, content_type) = if problem {
+        let problem = ProblemDetails::new(api_error, &errors.type_base_uri, res.request().path());
+        (serde_json::to_string(&problem)?, PROBLEM_JSON)
+    } else {
+        (serde_json::to_string(&api_error)?, "application/json")
+    };
+    let res = res.map_body(|head, _| {
+        head.headers_mut().remove(header::CONTENT_LENGTH);
+        head.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        // The format depends on `Accept`, caches must keep them apart.
+        head.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+        EitherBody::right(BoxBody::new(body))
+    });
+    Ok(ErrorHandlerResponse::Response(res))
+}
+
+// Explicitly asked for with `Accept`, otherwise the configured format.
+// A client that only accepts `application/json` keeps the ApiError shape.
+fn wants_problem<B>(res: &ServiceResponse<B>, errors: &ErrorSettings) -> bool {
+    let accepted: Vec<String> = res
+        .request()
+        .headers()
+        .get_all(header::ACCEPT)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| {
+            let mut params = media_range.split(';');
+            let media_type = params.next().unwrap_or_default().trim();
+            let refused = params.any(|param| matches!(param.trim(), "q=0" | "q=0.0" | "q=0.00" | "q=0.000"));
+            (!refused).then(|| media_type.to_ascii_lowercase())
+        })
+        .collect();
+    if accepted.iter().any(|media_type| media_type == PROBLEM_JSON) {
+        return true;
+    }
+    if accepted.iter().any(|media_type| media_type == "application/json") {
+        return false;
+    }
+    errors.format == "problem"
+}
+
+// `application/json`, or a JSON based type like `application/problem+json`.
+fn is_json<B>(res: &ServiceResponse<B>) -> bool {
+    let Some(content_type) = res.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    media_type == "application/json" || media_type.ends_with("+json")
+}
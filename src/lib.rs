@@ -0,0 +1,15 @@
+pub mod api;
+pub mod app;
+pub mod auth;
+pub mod config;
+pub mod constants;
+pub mod cors;
+pub mod handler;
+pub mod lifecycle;
+pub mod metrics;
+pub mod models;
+pub mod rate_limit;
+pub mod repository;
+pub mod request_id;
+pub mod services;
+pub mod telemetry;
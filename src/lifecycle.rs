@@ -0,0 +1,163 @@
+use std::future::Future;
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::Server;
+use actix_web::rt::signal::unix::{signal, SignalKind};
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time;
+use async_trait::async_trait;
+use futures::future::{self, Either};
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+use crate::config::settings::ShutdownSettings;
+use crate::services::health_service::HealthCheck;
+
+// Shutdown state shared by the server, the background jobs and the readiness probe.
+#[derive(Clone)]
+pub struct Shutdown {
+    started: Arc<watch::Sender<Option<Instant>>>,
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        Self {
+            started: Arc::new(watch::Sender::new(None)),
+        }
+    }
+
+    // Start the shutdown, only the first call counts.
+    pub fn start(&self) {
+        self.started.send_if_modified(|started| {
+            if started.is_some() {
+                return false;
+            }
+            *started = Some(Instant::now());
+            true
+        });
+    }
+
+    pub fn started_at(&self) -> Option<Instant> {
+        *self.started.borrow()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started_at().is_some()
+    }
+
+    // Resolves once the shutdown started.
+    pub async fn started(&self) {
+        // The sender is held by `self`, waiting can not fail.
+        let _ = self.started.subscribe().wait_for(Option::is_some).await;
+    }
+
+    // Output of `fut`, or None when the shutdown starts first.
+    pub async fn until<F: Future>(&self, fut: F) -> Option<F::Output> {
+        match future::select(pin!(fut), pin!(self.started())).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+// The app is unready as soon as the shutdown starts.
+#[async_trait]
+impl HealthCheck for Shutdown {
+    fn name(&self) -> &str {
+        "shutdown"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        if self.is_started() {
+            return Err("shutting down".to_owned());
+        }
+        Ok(())
+    }
+}
+
+// Owns the background jobs of the process and drains them with the server.
+pub struct Lifecycle {
+    shutdown: Shutdown,
+    grace_period: Duration,
+    drain_delay: Duration,
+    jobs: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl Lifecycle {
+    pub fn new(settings: &ShutdownSettings) -> Self {
+        Self {
+            shutdown: Shutdown::new(),
+            grace_period: Duration::from_secs(settings.grace_period_secs),
+            drain_delay: Duration::from_secs(settings.drain_delay_secs),
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    // Run a job on the current arbiter, it should return soon after `Shutdown::started`.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, job: F)
+    where
+        F: FnOnce(Shutdown) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let handle = actix_web::rt::spawn(job(self.shutdown.clone()));
+        self.jobs.push((name, handle));
+    }
+
+    // Serve until SIGTERM, SIGINT or `Shutdown::start`, then drain the server and the jobs.
+    // The server must be built with `disable_signals` and the grace period as `shutdown_timeout`.
+    pub async fn run(self, server: Server) -> io::Result<()> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let shutdown = self.shutdown.clone();
+        let drain_delay = self.drain_delay;
+        let handle = server.handle();
+        let watcher = actix_web::rt::spawn(async move {
+            let received = async {
+                match future::select(pin!(terminate.recv()), pin!(interrupt.recv())).await {
+                    Either::Left(_) => "SIGTERM",
+                    Either::Right(_) => "SIGINT",
+                }
+            };
+            match future::select(pin!(received), pin!(shutdown.started())).await {
+                Either::Left((name, _)) => info!("{} received, shutting down", name),
+                Either::Right(_) => info!("Shutting down"),
+            }
+            shutdown.start();
+
+            // Readiness fails from now on, load balancers get the delay to stop routing here
+            time::sleep(drain_delay).await;
+            handle.stop(true).await;
+        });
+
+        let result = server.await;
+        watcher.abort();
+        self.drain().await;
+        result
+    }
+
+    // Start the shutdown and wait for the jobs until the grace period ends, the rest are aborted.
+    pub async fn drain(self) {
+        self.shutdown.start();
+        let started = self.shutdown.started_at().unwrap_or_else(Instant::now);
+        let deadline = started + self.drain_delay + self.grace_period;
+        for (name, mut job) in self.jobs {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match time::timeout(remaining, &mut job).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!("Background job {} failed : {}", name, err),
+                Err(_) => {
+                    warn!("Background job {} still running after the grace period, aborting", name);
+                    job.abort();
+                }
+            }
+        }
+        info!("Shutdown complete");
+    }
+}
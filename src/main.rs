@@ -1,150 +1,131 @@
 use std::env;
-use actix_cors::Cors;
-use actix_web::dev::ServiceRequest;
-use actix_web::{
-    error::Error, error::InternalError, error::JsonPayloadError, http, web, HttpRequest, HttpResponse,
-};
-use actix_web::{middleware, web::Data, web::JsonConfig, App, HttpServer};
-use actix_web_grants::permissions::AttachPermissions;
-use actix_web_httpauth::extractors::bearer::BearerAuth;
-use actix_web_httpauth::middleware::HttpAuthentication;
-use chrono::{SecondsFormat, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use actix_web::HttpServer;
 use dotenvy::dotenv;
-use log::{info, warn};
-
-use models::error_model::ApiError;
-use models::task_model::{Task, TaskAggregate}; // Assuming TaskAggregate is added to handle aggregation
-use services::task_service::TaskService;
-use services::aggregator_service::AggregatorService;
-use crate::auth::claims::Claims;
-use crate::config::db;
-
-mod api;
-mod auth;
-mod config;
-mod constants;
-mod handler;
-mod models;
-mod repository;
-mod services;
+use log::{error, info};
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::services::trash_service;
+use actix_api::telemetry;
+use actix_api::config::db;
+use actix_api::config::migrations;
+use actix_api::config::settings::Settings;
+use actix_api::config::state::AppState;
+use actix_api::config::tls;
+use actix_api::lifecycle::Lifecycle;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize Log4rs
-    log4rs::init_file("log4rs.yml", Default::default()).unwrap();
-    info!("Initializing application...");
-
     // Load .env file
     dotenv().ok();
 
-    // Initialize MongoDB connection
-    let client = db::init().await;
-
-    // Initialize TaskService with MongoDB collection
-    let task_service = TaskService::new(client.database("users").collection::<Task>("tasks"));
-
-    // Get server host and port number from the environment file
-    let server_host = env::var("SERVER.HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let server_port: u16 = env::var("SERVER.PORT").unwrap_or_else(|_| "8080".to_string()).parse().unwrap_or(8080);
-
-    info!("Starting Actix-web server on {}:{}", server_host, server_port);
-
-    // Config and start Actix-web server
-    HttpServer::new(move || {
-        let auth = HttpAuthentication::bearer(validator);
-        App::new()
-            // Configure CORS
-            .wrap(
-                Cors::default()
-                    .allowed_origin("http://127.0.0.1:8080")
-                    .allowed_origin("http://localhost:8080")
-                    .send_wildcard()
-                    .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "PATCH"])
-                    .allowed_headers(vec![
-                        http::header::AUTHORIZATION,
-                        http::header::ACCEPT,
-                        http::header::CONTENT_TYPE,
-                    ])
-                    .max_age(3600),
-            )
-            // Configure compression
-            .wrap(middleware::Compress::default())
-            // Configure app data
-            .app_data(Data::new(client.clone()))
-            .app_data(JsonConfig::default().error_handler(json_error_handler))
-            .app_data(Data::new(task_service.clone())) // Pass TaskService into app state
-            // Configure un-secure controllers
-            .configure(api::init_auth_api)
-            .configure(api::init_ping_api)
-            .configure(api::init_location_api)
-            // Configure secure controller with JWT authentication under '/api' scope
-            .service(
-                web::scope("/api")
-                    .wrap(auth)
-                    .guard(check_auth)
-                    .configure(api::init_user_api)
-                    .configure(api::init_hello_api)
-                    .configure(api::init_task_api)
-                    .configure(api::init_aggregator_api) 
-            )
-           
-            .wrap(middleware::DefaultHeaders::new().add(("X-Version", "0.3.0")))
-            .wrap(middleware::Logger::default())
-    })
-    .bind((server_host, server_port))
-    .unwrap_or_else(|_| panic!("Error binding to port '{:?}'", server_port))
-    .run()
-    .await
-}
+    // Settings from the defaults, the settings file and the environment.
+    // Logging is not up yet, so errors go to stderr.
+    let settings = match Settings::load() {
+        Ok(settings) => Arc::new(settings),
+        Err(err) => {
+            eprintln!("{}", err);
+            return Err(std::io::Error::other(err));
+        }
+    };
+    if env::args().any(|arg| arg == "--print-config") {
+        println!("{}", serde_json::to_string_pretty(&settings.redacted()).unwrap());
+        return Ok(());
+    }
+
+    // Initialize Log4rs, `log4rs-json.yml` logs JSON records instead
+    log4rs::init_file(&settings.log.config, Default::default()).unwrap();
+    info!("Initializing application...");
 
-fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> Error {
-    let detail = err.to_string();
-    let resp = match &err {
-        JsonPayloadError::ContentType => HttpResponse::UnsupportedMediaType().json(ApiError {
-            status: 415,
-            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
-            message: "Unsupported media type".to_owned(),
-            debug_message: Some(detail),
-            sub_errors: Vec::new(),
-        }),
-        JsonPayloadError::Deserialize(json_err) if json_err.is_data() => HttpResponse::UnprocessableEntity().json(ApiError {
-            status: 422,
-            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
-            message: "Unprocessable payload".to_owned(),
-            debug_message: Some(detail),
-            sub_errors: Vec::new(),
-        }),
-        _ => HttpResponse::BadRequest().json(ApiError {
-            status: 400,
-            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
-            message: "Bad request. Missing parameter and/or wrong payload.".to_owned(),
-            debug_message: Some(detail),
-            sub_errors: Vec::new(),
-        }),
+    // Tracing, exported as configured
+    let tracer_provider = match telemetry::init(&settings.otel) {
+        Ok(provider) => provider,
+        Err(err) => {
+            error!("Error initializing tracing : {}", err);
+            return Err(std::io::Error::other(err));
+        }
     };
-    InternalError::from_response(err, resp).into()
-}
 
-async fn validator(
-    req: ServiceRequest,
-    credentials: BearerAuth,
-) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    warn!("Validating JWT auth");
-    let result = Claims::decode_jwt(credentials.token());
-    match result {
-        Ok(claims) => {
-            req.attach(claims.permissions);
-            Ok(req)
+    // Initialize MongoDB connection
+    let client = match db::init(&settings.mongo).await {
+        Ok(client) => client,
+        Err(err) => {
+            error!("{}", err);
+            return Err(std::io::Error::other(err));
         }
-        Err(e) => Err((e, req)),
+    };
+
+    // Application state with every collection on the configured database
+    let state = AppState::new(client, settings.clone());
+
+    // Apply pending migrations, `--migrate-only` stops right after them
+    if let Err(err) = migrations::run(&state).await {
+        error!("Error applying migrations : {}", err);
+        return Err(std::io::Error::other(err));
     }
-}
+    if env::args().any(|arg| arg == "--migrate-only") {
+        info!("Migrations applied, exiting");
+        return Ok(());
+    }
+
+    // Background jobs, drained with the server on shutdown
+    let mut lifecycle = Lifecycle::new(&settings.shutdown);
+
+    // Repositories on MongoDB, shared by every worker, unready once shutting down
+    let mut repos = Repositories::mongo(&state);
+    repos.health.register(lifecycle.shutdown());
+
+    // Purge expired trash in the background, a purge in progress is completed on shutdown
+    let retention_days = settings.trash.retention_days;
+    let purge_interval = Duration::from_secs(settings.trash.purge_interval_secs);
+    let purge_repo = repos.trash.clone();
+    lifecycle.spawn("trash purge", move |shutdown| async move {
+        let mut interval = actix_web::rt::time::interval(purge_interval);
+        while shutdown.until(interval.tick()).await.is_some() {
+            trash_service::purge_expired(purge_repo.as_ref(), retention_days).await;
+        }
+    });
 
-use actix_web::guard::GuardContext;
+    // Server host and port number
+    let server_host = settings.server.host.clone();
+    let server_port = settings.server.port;
 
-fn check_auth(ctx: &GuardContext) -> bool {
-    if let Some(auth_header) = ctx.head().headers().get(http::header::AUTHORIZATION) {
-        return auth_header.to_str().is_ok();
+    // TLS config, the certificate is reloaded on SIGHUP
+    let tls_config = if settings.tls.enabled {
+        let (config, resolver) = tls::server_config(&settings.tls).map_err(|err| {
+            error!("Error loading TLS config : {}", err);
+            std::io::Error::other(err)
+        })?;
+        tls::reload_on_sighup(resolver)?;
+        Some(config)
+    } else {
+        None
+    };
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+
+    info!("Starting Actix-web server on {}://{}:{}", scheme, server_host, server_port);
+
+    // Config and start Actix-web server, HTTP/2 is negotiated over TLS.
+    // Signals are handled by the lifecycle, which stops the server gracefully.
+    let grace_period_secs = settings.shutdown.grace_period_secs;
+    let server = HttpServer::new(move || create_app(settings.clone(), repos.clone()))
+    .disable_signals()
+    .shutdown_timeout(grace_period_secs);
+    let server = match tls_config {
+        Some(config) => server.bind_rustls_0_23((server_host, server_port), config),
+        None => server.bind((server_host, server_port)),
+    };
+    let server = server
+    .unwrap_or_else(|_| panic!("Error binding to port '{:?}'", server_port))
+    .run();
+    let result = lifecycle.run(server).await;
+
+    // Flush the spans still buffered
+    if let Some(provider) = tracer_provider {
+        if let Err(err) = provider.shutdown() {
+            error!("Error shutting down tracing : {}", err);
+        }
     }
-    false
+    result
 }
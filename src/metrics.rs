@@ -0,0 +1,186 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramTimer, HistogramVec,
+    IntCounterVec, TextEncoder,
+};
+
+// Application metrics, all in the default Prometheus registry and served by `/metrics`.
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests by method, route pattern and status",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latency by method, route pattern and status",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static MONGO_OPERATION_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "mongo_operation_duration_seconds",
+        "MongoDB latency by repository and function",
+        &["repository", "operation"]
+    )
+    .unwrap()
+});
+
+pub static PASSWORD_HASH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "argon2_duration_seconds",
+        "Argon2 time to hash or verify a password",
+        &["operation"],
+        vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    )
+    .unwrap()
+});
+
+pub static LOGINS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("auth_logins_total", "Logins by result", &["result"]).unwrap()
+});
+
+pub static RATE_LIMITED_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rate_limited_requests_total",
+        "Requests refused with 429 by route pattern of the rule",
+        &["route"]
+    )
+    .unwrap()
+});
+
+pub static OUTBOUND_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "outbound_http_requests_total",
+        "Outbound HTTP calls by service and status, `error` when no response came back",
+        &["service", "status"]
+    )
+    .unwrap()
+});
+
+pub static OUTBOUND_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "outbound_http_request_duration_seconds",
+        "Outbound HTTP call latency by service",
+        &["service"]
+    )
+    .unwrap()
+});
+
+// Observes the latency of a repository function when dropped.
+pub fn mongo_timer(repository: &str, operation: &str) -> HistogramTimer {
+    MONGO_OPERATION_DURATION
+        .with_label_values(&[repository, operation])
+        .start_timer()
+}
+
+pub fn password_timer(operation: &str) -> HistogramTimer {
+    PASSWORD_HASH_DURATION
+        .with_label_values(&[operation])
+        .start_timer()
+}
+
+pub fn login(success: bool) {
+    let result = if success { "success" } else { "failure" };
+    LOGINS.with_label_values(&[result]).inc();
+}
+
+// Time an outbound call and count it by response status.
+pub async fn outbound<F>(service: &str, call: F) -> Result<reqwest::Response, reqwest::Error>
+where
+    F: Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    let timer = OUTBOUND_REQUEST_DURATION
+        .with_label_values(&[service])
+        .start_timer();
+    let response = call.await;
+    timer.observe_duration();
+    let status = match &response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_owned(),
+    };
+    OUTBOUND_REQUESTS.with_label_values(&[service, &status]).inc();
+    response
+}
+
+// Every metric in the Prometheus text format.
+pub fn render() -> Result<String, prometheus::Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+// Middleware counting and timing every request by its route pattern,
+// so `/api/tasks/{id}` is one series whatever the id.
+pub struct HttpMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for HttpMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = HttpMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpMetricsMiddleware { service }))
+    }
+}
+
+pub struct HttpMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        // Unmatched requests share one series instead of one per path.
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let future = self.service.call(req);
+        Box::pin(async move {
+            let result = future.await;
+            let status = match &result {
+                Ok(response) => response.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            let status = status.as_u16().to_string();
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+            HTTP_REQUESTS.with_label_values(&labels).inc();
+            HTTP_REQUEST_DURATION
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use serde::{Deserialize, Serialize};
+
+use crate::models::error_model::{ApiError, ApiErrorType};
+use crate::models::task_model::Task;
+
+// One operation of a bulk task request, tagged by `op`.
+// `version` is an optional optimistic concurrency check, like `If-Match`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BulkTaskOperation {
+    Create {
+        task: Task,
+    },
+    Update {
+        id: String,
+        task: Task,
+        version: Option<i64>,
+    },
+    Delete {
+        id: String,
+        version: Option<i64>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTaskRequest {
+    pub operations: Vec<BulkTaskOperation>,
+}
+
+// Outcome of one operation, reported in request order.
+#[derive(Debug, Serialize)]
+pub struct BulkItemResult {
+    pub index: usize,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+impl BulkItemResult {
+    pub fn success(index: usize, id: Option<String>, status: StatusCode) -> Self {
+        Self {
+            index,
+            status: status.as_u16(),
+            id,
+            error: None,
+        }
+    }
+
+    pub fn failure(index: usize, id: Option<String>, error: &ApiErrorType) -> Self {
+        Self {
+            index,
+            status: error.status_code().as_u16(),
+            id,
+            error: Some(error.api_error()),
+        }
+    }
+
+    pub fn redact(&mut self) {
+        if let Some(error) = self.error.as_mut() {
+            error.redact();
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResponse {
+    pub atomic: bool,
+    pub results: Vec<BulkItemResult>,
+}
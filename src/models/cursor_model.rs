@@ -0,0 +1,43 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::models::error_model::ApiErrorType;
+
+// Opaque keyset pagination cursor.
+// Holds the sort key and `_id` of the row the page starts after (or before).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(rename = "k")]
+    pub key: String,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(key: &str, id: &str) -> Self {
+        Self {
+            key: key.to_owned(),
+            id: id.to_owned(),
+        }
+    }
+
+    // Encode as URL safe base64 JSON so clients treat it as an opaque token.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    // Decode a cursor from the query string.
+    // An empty value means "from the start" (or "from the end" for `before`).
+    pub fn decode(value: &str) -> Result<Option<Self>, ApiErrorType> {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let json = URL_SAFE_NO_PAD
+            .decode(value)
+            .map_err(|_| ApiErrorType::BadRequest)?;
+        serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|_| ApiErrorType::BadRequest)
+    }
+}
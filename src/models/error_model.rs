@@ -1,14 +1,23 @@
+use std::error::Error as StdError;
+
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
 use chrono::{SecondsFormat, Utc};
-use derive_more::{Display, Error};
+use derive_more::Display;
+use log::error;
+use nanoid::nanoid;
 use serde::Serialize;
 use validator::ValidationErrors;
 
+use crate::request_id;
+
+// Cause of an internal error. It is logged, clients only get the id of the log record.
+pub type ErrorSource = Box<dyn StdError + Send + Sync>;
+
 // -- Error handing.
-#[derive(Debug, Display, Error)]
+#[derive(Debug, Display)]
 pub enum ApiErrorType {
     #[display(fmt = "Internal server error. Try again after some time.")]
-    InternalServerError,
+    InternalServerError { source: ErrorSource },
 
     #[display(fmt = "Bad request.")]
     BadRequest,
@@ -23,7 +32,7 @@ pub enum ApiErrorType {
     AuthenticationError,
 
     #[display(fmt = "Aggregator Error")]
-    AggregatorError,
+    AggregatorError { source: ErrorSource },
 
     // Figure out a way to use this when JWT does not have authorization.
     #[display(fmt = "Authorization error.")]
@@ -37,6 +46,33 @@ pub enum ApiErrorType {
 
     #[display(fmt = "Invalid credential.")]
     InvalidCredential,
+
+    #[display(fmt = "Precondition failed.")]
+    PreconditionFailed,
+
+    #[display(fmt = "Operation not applied.")]
+    BulkAborted,
+
+    #[display(fmt = "Too many requests.")]
+    TooManyRequests,
+
+    #[display(fmt = "Resource not found.")]
+    RouteNotFound,
+
+    #[display(fmt = "Method not allowed.")]
+    MethodNotAllowed,
+}
+
+// The boxed source is not supported by the derive, it is returned by hand.
+impl StdError for ApiErrorType {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ApiErrorType::InternalServerError { source } | ApiErrorType::AggregatorError { source } => {
+                Some(source.as_ref())
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -49,20 +85,117 @@ pub struct ValidationError {
 
 #[derive(Debug, Serialize)]
 pub struct ApiError {
+    // Slug of the problem `type` URI, `about:blank` without one.
+    #[serde(skip)]
+    pub problem_type: Option<&'static str>,
     pub status: u16,
     pub time: String,
     pub message: String,
     pub debug_message: Option<String>,
     pub sub_errors: Vec<ValidationError>,
+    // `X-Request-Id` of the failed request, to find its log lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // Id of the log record of an internal error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
+}
+
+impl ApiError {
+    // Standard payload for an error response that did not come from an `ApiErrorType`.
+    // Statuses with a matching type use its messages, the others their reason phrase.
+    pub fn from_status(status: StatusCode, detail: Option<String>) -> ApiError {
+        let error_type = match status {
+            StatusCode::BAD_REQUEST => Some(ApiErrorType::BadRequest),
+            StatusCode::UNAUTHORIZED => Some(ApiErrorType::AuthenticationError),
+            StatusCode::FORBIDDEN => Some(ApiErrorType::AuthorizationError),
+            StatusCode::NOT_FOUND => Some(ApiErrorType::RouteNotFound),
+            StatusCode::METHOD_NOT_ALLOWED => Some(ApiErrorType::MethodNotAllowed),
+            StatusCode::TOO_MANY_REQUESTS => Some(ApiErrorType::TooManyRequests),
+            StatusCode::INTERNAL_SERVER_ERROR => Some(ApiErrorType::internal(
+                detail.clone().unwrap_or_else(|| "no error attached to the response".to_owned()),
+            )),
+            _ => None,
+        };
+        let mut api_error = match error_type {
+            Some(error_type) => error_type.api_error(),
+            None => ApiError {
+                problem_type: None,
+                status: status.as_u16(),
+                time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+                message: format!("{}.", status.canonical_reason().unwrap_or("Error")),
+                debug_message: None,
+                sub_errors: Vec::new(),
+                request_id: request_id::current(),
+                error_id: None,
+            },
+        };
+        // The original error says more than the generic message, e.g. which parameter is wrong.
+        if let Some(detail) = detail.filter(|detail| !detail.is_empty()) {
+            api_error.debug_message = Some(detail);
+        }
+        api_error
+    }
+
+    // Drop the debug message, only sent to clients when `errors.expose_details` is set.
+    pub fn redact(&mut self) {
+        self.debug_message = None;
+    }
+
+    // JSON response, carrying the error for the middleware to render it as negotiated.
+    pub fn into_response(self, status: StatusCode) -> HttpResponse {
+        let mut response = HttpResponse::build(status).json(&self);
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+// RFC 7807 document of an error, served as `application/problem+json`.
+// `time`, `request_id` and the validation `errors` are extension members.
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub instance: String,
+    pub time: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ValidationError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
+}
+
+impl ProblemDetails {
+    // `type_base_uri` prefixes the problem type of the error, `instance` is the request path.
+    pub fn new(error: ApiError, type_base_uri: &str, instance: &str) -> Self {
+        let problem_type = match error.problem_type {
+            Some(slug) => format!("{}/{}", type_base_uri.trim_end_matches('/'), slug),
+            None => "about:blank".to_owned(),
+        };
+        Self {
+            problem_type,
+            title: error.message,
+            status: error.status,
+            detail: error.debug_message,
+            instance: instance.to_owned(),
+            time: error.time,
+            errors: error.sub_errors,
+            request_id: error.request_id,
+            error_id: error.error_id,
+        }
+    }
 }
 
 // Set Debug Error messages for Global error.
 impl ApiErrorType {
     fn debug_message(&self) -> String {
         match self {
-            ApiErrorType::InternalServerError => {
-                "Internal server error. Please try again later.".to_owned()
-            }
+            ApiErrorType::InternalServerError { source } => describe(source.as_ref()),
             ApiErrorType::BadRequest => {
                 "Bad request. Missing parameter or wrong payload.".to_owned()
             }
@@ -71,7 +204,10 @@ impl ApiErrorType {
             ApiErrorType::AuthenticationError => {
                 "User not authenticated. Please reauthenticate and try again.".to_owned()
             }
-            ApiErrorType::AggregatorError => {"MongoDB Aggregator Pipeline Error, Could'nt aggregate users-tasks".to_owned()}
+            ApiErrorType::AggregatorError { source } => format!(
+                "MongoDB aggregation pipeline error. Could not compute task statistics: {}",
+                describe(source.as_ref())
+            ),
             ApiErrorType::AuthorizationError => {
                 "User not authorized to access this resource.".to_owned()
             }
@@ -79,28 +215,49 @@ impl ApiErrorType {
             ApiErrorType::InvalidCredential => {
                 "Invalid Credential. Checking email address and password".to_owned()
             }
+            ApiErrorType::PreconditionFailed => {
+                "Resource was modified since it was fetched. Fetch it again and retry.".to_owned()
+            }
+            ApiErrorType::BulkAborted => {
+                "Another operation of the atomic request failed. Nothing was written.".to_owned()
+            }
+            ApiErrorType::TooManyRequests => {
+                "Rate limit exceeded. Retry after the number of seconds in Retry-After.".to_owned()
+            }
+            ApiErrorType::RouteNotFound => "No route matches the requested path.".to_owned(),
+            ApiErrorType::MethodNotAllowed => {
+                "The route does not support the request method.".to_owned()
+            }
         }
     }
-}
 
-impl ResponseError for ApiErrorType {
-    fn status_code(&self) -> StatusCode {
-        match *self {
-            ApiErrorType::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
-            ApiErrorType::BadRequest => StatusCode::BAD_REQUEST,
-            ApiErrorType::UserNotFound => StatusCode::NOT_FOUND,
-            ApiErrorType::TaskNotFound => StatusCode::NOT_FOUND,
-            ApiErrorType::AuthenticationError => StatusCode::UNAUTHORIZED,
-            ApiErrorType::AuthorizationError => StatusCode::FORBIDDEN,
-            ApiErrorType::AggregatorError => StatusCode:: INTERNAL_SERVER_ERROR,
-            ApiErrorType::ValidationError { .. } => StatusCode::UNPROCESSABLE_ENTITY,
-            ApiErrorType::InvalidCredential => StatusCode::UNAUTHORIZED,
-            ApiErrorType::AggregatorError => todo!(),
+    // Last segment of the problem `type` URI, stable once published.
+    pub fn problem_type(&self) -> &'static str {
+        match self {
+            ApiErrorType::InternalServerError { .. } => "internal-server-error",
+            ApiErrorType::BadRequest => "bad-request",
+            ApiErrorType::UserNotFound => "user-not-found",
+            ApiErrorType::TaskNotFound => "task-not-found",
+            ApiErrorType::AuthenticationError => "authentication-error",
+            ApiErrorType::AggregatorError { .. } => "aggregator-error",
+            ApiErrorType::AuthorizationError => "authorization-error",
+            ApiErrorType::ValidationError { .. } => "validation-error",
+            ApiErrorType::InvalidCredential => "invalid-credential",
+            ApiErrorType::PreconditionFailed => "precondition-failed",
+            ApiErrorType::BulkAborted => "bulk-aborted",
+            ApiErrorType::TooManyRequests => "too-many-requests",
+            ApiErrorType::RouteNotFound => "route-not-found",
+            ApiErrorType::MethodNotAllowed => "method-not-allowed",
         }
     }
 
-    // Global error handler Http Response payload
-    fn error_response(&self) -> HttpResponse {
+    pub fn internal(source: impl Into<ErrorSource>) -> Self {
+        ApiErrorType::InternalServerError { source: source.into() }
+    }
+
+    // Standard error payload, also used for per item errors of bulk requests.
+    // Internal errors are logged here, once, under the error id given to the client.
+    pub fn api_error(&self) -> ApiError {
         let mut validation_sub_errs = vec![];
         match self {
             // Iterate thru validation error object
@@ -123,12 +280,60 @@ impl ResponseError for ApiErrorType {
                 validation_sub_errs = vec![];
             }
         }
-        HttpResponse::build(self.status_code()).json(ApiError {
+        let error_id = match self {
+            ApiErrorType::InternalServerError { .. } | ApiErrorType::AggregatorError { .. } => {
+                let error_id = nanoid!();
+                error!("Internal error {} : {}", error_id, self.debug_message());
+                Some(error_id)
+            }
+            _ => None,
+        };
+        ApiError {
+            problem_type: Some(self.problem_type()),
             status: self.status_code().as_u16(),
             time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
             message: self.to_string(),
             debug_message: Some(self.debug_message()),
             sub_errors: validation_sub_errs,
-        })
+            request_id: request_id::current(),
+            error_id,
+        }
+    }
+}
+
+impl ResponseError for ApiErrorType {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            ApiErrorType::InternalServerError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiErrorType::BadRequest => StatusCode::BAD_REQUEST,
+            ApiErrorType::UserNotFound => StatusCode::NOT_FOUND,
+            ApiErrorType::TaskNotFound => StatusCode::NOT_FOUND,
+            ApiErrorType::AuthenticationError => StatusCode::UNAUTHORIZED,
+            ApiErrorType::AuthorizationError => StatusCode::FORBIDDEN,
+            ApiErrorType::AggregatorError { .. } => StatusCode:: INTERNAL_SERVER_ERROR,
+            ApiErrorType::ValidationError { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiErrorType::InvalidCredential => StatusCode::UNAUTHORIZED,
+            ApiErrorType::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiErrorType::BulkAborted => StatusCode::FAILED_DEPENDENCY,
+            ApiErrorType::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ApiErrorType::RouteNotFound => StatusCode::NOT_FOUND,
+            ApiErrorType::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+        }
+    }
+
+    // Global error handler Http Response payload, as `ApiError` or RFC 7807 per the request.
+    fn error_response(&self) -> HttpResponse {
+        self.api_error().into_response(self.status_code())
+    }
+}
+
+// The error and its causes, outermost first.
+fn describe(error: &(dyn StdError + 'static)) -> String {
+    let mut description = error.to_string();
+    let mut cause = error.source();
+    while let Some(error) = cause {
+        description.push_str(&format!(" : {}", error));
+        cause = error.source();
     }
+    description
 }
@@ -0,0 +1,42 @@
+use actix_web::http::header::{EntityTag, IfMatch, IfNoneMatch};
+use bson::{doc, Bson, Document};
+
+// Optimistic concurrency helpers.
+// A document's `version` is exposed as a strong ETag and checked on writes.
+
+pub fn etag(version: i64) -> EntityTag {
+    EntityTag::new_strong(version.to_string())
+}
+
+// Versions an `If-Match` header accepts. `None` when there is no precondition.
+// A missing header parses as an empty list, and `*` only requires the document to exist.
+pub fn if_match_versions(if_match: Option<IfMatch>) -> Option<Vec<i64>> {
+    match if_match {
+        Some(IfMatch::Items(tags)) if !tags.is_empty() => Some(
+            tags.iter()
+                .filter(|tag| !tag.weak)
+                .filter_map(|tag| tag.tag().parse().ok())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// True when the client already holds this version, per `If-None-Match`.
+pub fn is_not_modified(if_none_match: Option<IfNoneMatch>, version: i64) -> bool {
+    match if_none_match {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag(version))),
+        None => false,
+    }
+}
+
+// Mongo filter on the accepted versions.
+// Documents written before versioning have no `version` field and count as version 0.
+pub fn version_filter(versions: &[i64]) -> Document {
+    let mut accepted: Vec<Bson> = versions.iter().map(|v| Bson::Int64(*v)).collect();
+    if versions.contains(&0) {
+        accepted.push(Bson::Null);
+    }
+    doc! { "$in": accepted }
+}
@@ -0,0 +1,120 @@
+use actix_web::web::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::models::bulk_model::BulkItemResult;
+use crate::models::error_model::ApiErrorType;
+
+// File formats for exporting and importing tasks and users.
+// CSV has a header row, NDJSON has one JSON object per line.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    // Format of an upload without a `format` query parameter.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "text/csv" => Some(ExportFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" => Some(ExportFormat::Ndjson),
+            _ => None,
+        }
+    }
+
+    // First chunk of an export, before any row.
+    pub fn header(&self, columns: &[&str]) -> Bytes {
+        match self {
+            ExportFormat::Csv => Bytes::from(format!("{}\n", columns.join(","))),
+            ExportFormat::Ndjson => Bytes::new(),
+        }
+    }
+
+    // Encode one row, terminated by a line break so rows can be streamed one by one.
+    pub fn encode<T: Serialize>(&self, row: &T) -> Bytes {
+        match self {
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                let _ = writer.serialize(row);
+                Bytes::from(writer.into_inner().unwrap_or_default())
+            }
+            ExportFormat::Ndjson => {
+                let mut line = serde_json::to_vec(row).unwrap_or_default();
+                line.push(b'\n');
+                Bytes::from(line)
+            }
+        }
+    }
+
+    // Decode every row of an upload. A row that can not be parsed yields its error message.
+    // Blank NDJSON lines are skipped, row indexes count data rows only.
+    pub fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Vec<Result<T, String>> {
+        match self {
+            ExportFormat::Csv => csv::Reader::from_reader(body)
+                .deserialize()
+                .map(|row| row.map_err(|err| err.to_string()))
+                .collect(),
+            ExportFormat::Ndjson => body
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                .map(|line| serde_json::from_slice(line).map_err(|err| err.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportOptions {
+    // Falls back to the request `Content-Type` when not set.
+    pub format: Option<ExportFormat>,
+}
+
+// Outcome of an import, one result per data row in upload order.
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+impl ImportResponse {
+    pub fn new(results: Vec<BulkItemResult>) -> Self {
+        let imported = results.iter().filter(|result| result.error.is_none()).count();
+        Self {
+            imported,
+            failed: results.len() - imported,
+            results,
+        }
+    }
+}
+
+// Result for a row that could not be parsed, with the parser message as debug message.
+pub fn parse_error(index: usize, message: String) -> BulkItemResult {
+    let mut result = BulkItemResult::failure(index, None, &ApiErrorType::BadRequest);
+    if let Some(error) = result.error.as_mut() {
+        error.debug_message = Some(message);
+    }
+    result
+}
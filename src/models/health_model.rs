@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+// Response of the health endpoints.
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub version: String,
+    // Only reported by the readiness endpoint.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<CheckReport>,
+}
+
+// Outcome of one dependency check.
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub name: String,
+    pub status: HealthStatus,
+    // An unhealthy optional dependency does not make the app unready.
+    pub required: bool,
+    pub latency_ms: u128,
+    // Id of the logged failure, to find the reason in the logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
+    // Only sent when `errors.expose_details` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -1,6 +1,16 @@
 pub mod auth_model;
+pub mod bulk_model;
+pub mod cursor_model;
 pub mod error_model;
+pub mod etag_model;
+pub mod export_model;
+pub mod health_model;
 pub mod location_model;
+pub mod page_response;
+pub mod rate_limit_model;
+pub mod patch_model;
+pub mod stats_model;
+pub mod trash_model;
 pub mod user_list_response;
 pub mod user_model;
 pub mod task_model;
@@ -0,0 +1,236 @@
+use actix_web::{web, HttpRequest};
+use serde::Serialize;
+
+use crate::config::settings::PaginationSettings;
+use crate::models::cursor_model::Cursor;
+use crate::models::error_model::ApiErrorType;
+
+// Query parameters owned by the pagination links.
+// Every other query parameter of the request is kept as is.
+const PAGING_PARAMS: [&str; 4] = ["offset", "limit", "after", "before"];
+
+// Generic paginated list response shared by all list endpoints.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub meta: Meta,
+    pub _link: Link,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Meta {
+    // Not set for cursor based pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    pub limit: i64,
+    // Only counted for cursor based pages when asked with `count=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_results: Option<u64>,
+    pub search_criteria: Option<String>,
+    pub sort_by: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Link {
+    pub first: LinkHref,
+    pub last: LinkHref,
+    pub previous: Option<LinkHref>,
+    pub next: Option<LinkHref>,
+    pub self_link: LinkHref,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkHref {
+    pub href: String,
+}
+
+// Limit of a list request, `pagination.default_limit` when not sent.
+// Limits below 1 or above `pagination.max_limit` are rejected.
+pub fn page_limit(requested: Option<i64>, settings: &PaginationSettings) -> Result<i64, ApiErrorType> {
+    let limit = requested.unwrap_or(settings.default_limit);
+    if limit <= 0 || limit > settings.max_limit {
+        return Err(ApiErrorType::BadRequest);
+    }
+    Ok(limit)
+}
+
+// Where a cursor based list request starts and which way it pages.
+pub struct Keyset {
+    pub cursor: Option<Cursor>,
+    pub backward: bool,
+    limit: i64,
+}
+
+impl Keyset {
+    // `before` pages backward from its cursor, `after` forward.
+    pub fn from_query(
+        after: Option<&str>,
+        before: Option<&str>,
+        limit: i64,
+    ) -> Result<Self, ApiErrorType> {
+        let backward = before.is_some();
+        let raw_cursor = if backward { before } else { after };
+        Ok(Self {
+            cursor: Cursor::decode(raw_cursor.unwrap_or_default())?,
+            backward,
+            limit,
+        })
+    }
+
+    // Fetch one extra row to know whether there is another page in this direction.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit.saturating_add(1)
+    }
+}
+
+enum Mode {
+    Offset(u64),
+    Cursor {
+        previous: Option<String>,
+        next: Option<String>,
+    },
+}
+
+// Builds a `Page` with links derived from the request URL and query string.
+pub struct PageBuilder {
+    path: String,
+    query: Vec<(String, String)>,
+    limit: i64,
+    total: Option<u64>,
+    mode: Mode,
+}
+
+impl PageBuilder {
+    pub fn new(req: &HttpRequest, limit: i64) -> Self {
+        let query = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default();
+        Self {
+            path: req.path().to_owned(),
+            query,
+            limit,
+            total: None,
+            mode: Mode::Offset(0),
+        }
+    }
+
+    pub fn total(mut self, total: Option<u64>) -> Self {
+        self.total = total;
+        self
+    }
+
+    // Offset based links: first/last/previous/next are computed from the total.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.mode = Mode::Offset(offset);
+        self
+    }
+
+    // Cursor based links: `previous` and `next` are the cursors to page from, if any.
+    pub fn cursors(mut self, previous: Option<String>, next: Option<String>) -> Self {
+        self.mode = Mode::Cursor { previous, next };
+        self
+    }
+
+    // Cursor based page from rows fetched with `Keyset::fetch_limit`, in fetch order.
+    // `key` gives the cursor of a row for the previous and next links.
+    pub fn keyset<T>(self, keyset: &Keyset, mut rows: Vec<T>, key: impl Fn(&T) -> Cursor) -> Page<T> {
+        let has_more = rows.len() as i64 > keyset.limit;
+        rows.truncate(keyset.limit as usize);
+        if keyset.backward {
+            rows.reverse();
+        }
+        let (has_previous, has_next) = if keyset.backward {
+            (has_more, keyset.cursor.is_some())
+        } else {
+            (keyset.cursor.is_some(), has_more)
+        };
+
+        let previous = rows.first().filter(|_| has_previous).map(|r| key(r).encode());
+        let next = rows.last().filter(|_| has_next).map(|r| key(r).encode());
+        self.cursors(previous, next).build(rows)
+    }
+
+    pub fn build<T>(self, data: Vec<T>) -> Page<T> {
+        let limit = self.limit.max(1) as u64;
+        let (offset, link) = match &self.mode {
+            Mode::Offset(offset) => {
+                let offset = *offset;
+                let total = self.total.unwrap_or(0);
+                let last_offset = if total == 0 {
+                    0
+                } else {
+                    ((total - 1) / limit) * limit
+                };
+                let next_offset = offset.checked_add(limit);
+                let link = Link {
+                    first: self.href("offset", "0"),
+                    last: self.href("offset", &last_offset.to_string()),
+                    previous: if offset == 0 {
+                        None
+                    } else {
+                        Some(self.href("offset", &offset.saturating_sub(limit).to_string()))
+                    },
+                    next: next_offset
+                        .filter(|next| *next <= last_offset)
+                        .map(|next| self.href("offset", &next.to_string())),
+                    self_link: self.href("offset", &offset.to_string()),
+                };
+                (Some(offset), link)
+            }
+            Mode::Cursor { previous, next } => {
+                let link = Link {
+                    first: self.href("after", ""),
+                    last: self.href("before", ""),
+                    previous: previous.as_ref().map(|c| self.href("before", c)),
+                    next: next.as_ref().map(|c| self.href("after", c)),
+                    self_link: self.self_href(),
+                };
+                (None, link)
+            }
+        };
+
+        Page {
+            data,
+            meta: Meta {
+                offset,
+                limit: self.limit,
+                total_results: self.total,
+                search_criteria: None,
+                sort_by: None,
+            },
+            _link: link,
+        }
+    }
+
+    // Link to the same resource with the given paging parameter and the current limit.
+    fn href(&self, param: &str, value: &str) -> LinkHref {
+        let mut pairs = vec![
+            (param.to_owned(), value.to_owned()),
+            ("limit".to_owned(), self.limit.to_string()),
+        ];
+        pairs.extend(
+            self.query
+                .iter()
+                .filter(|(key, _)| !PAGING_PARAMS.contains(&key.as_str()))
+                .cloned(),
+        );
+        LinkHref {
+            href: format!(
+                "{}?{}",
+                self.path,
+                serde_urlencoded::to_string(pairs).unwrap_or_default()
+            ),
+        }
+    }
+
+    // Link to the current cursor page, keeping whichever cursor the request used.
+    fn self_href(&self) -> LinkHref {
+        let cursor = ["before", "after"]
+            .iter()
+            .find_map(|name| self.query.iter().find(|(key, _)| key == name));
+        match cursor {
+            Some((key, value)) => self.href(key, value),
+            None => self.href("after", ""),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use bson::{Bson, Document};
+use serde::{de::Error, Deserialize, Deserializer};
+
+// Helpers for JSON Merge Patch (RFC 7396) payloads.
+// Patch structs use `#[serde(default)]` so a missing member stays `None` and is left untouched.
+// Required members use `non_null`, optional ones `nullable` so that `null` removes them.
+
+// Reject `null` for members that can not be removed from the document.
+pub fn non_null<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    match Option::<T>::deserialize(deserializer)? {
+        Some(value) => Ok(Some(value)),
+        None => Err(D::Error::custom("member can not be removed with null")),
+    }
+}
+
+// Accept `null` for members that can be removed, as `Some(None)`.
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Translate a serialized patch into a targeted Mongo update.
+// `null` members, the `Some(None)` of `nullable`, are unset, every other member is set.
+pub fn to_update_doc(patch: Document) -> Document {
+    let mut set = Document::new();
+    let mut unset = Document::new();
+    for (field, value) in patch {
+        match value {
+            Bson::Null => unset.insert(field, ""),
+            value => set.insert(field, value),
+        };
+    }
+
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    update
+}
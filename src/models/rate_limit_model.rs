@@ -0,0 +1,86 @@
+// Token bucket of one rule: bursts of `capacity` requests, refilled continuously over `period_secs`.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub capacity: u32,
+    pub period_secs: u64,
+}
+
+impl Quota {
+    // Tokens added back every millisecond.
+    pub fn refill_per_ms(&self) -> f64 {
+        self.capacity as f64 / (self.period_secs * 1000) as f64
+    }
+
+    // Whole seconds for a bucket holding `tokens` to reach `target`.
+    fn secs_until(&self, tokens: f64, target: f64) -> u64 {
+        if tokens >= target {
+            return 0;
+        }
+        ((target - tokens) / self.refill_per_ms() / 1000.0).ceil() as u64
+    }
+}
+
+// A bucket right after a request took, or could not take, its token.
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub allowed: bool,
+    pub tokens: f64,
+}
+
+impl Bucket {
+    pub fn remaining(&self) -> u32 {
+        self.tokens.floor() as u32
+    }
+
+    // Seconds until the bucket is full again.
+    pub fn reset_secs(&self, quota: &Quota) -> u64 {
+        quota.secs_until(self.tokens, quota.capacity as f64)
+    }
+
+    // Seconds until the next token, for a refused request.
+    pub fn retry_after_secs(&self, quota: &Quota) -> u64 {
+        quota.secs_until(self.tokens, 1.0).max(1)
+    }
+}
+
+// Bucket state kept between requests by the in-memory store.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated_ms: i64,
+}
+
+impl TokenBucket {
+    pub fn full(quota: &Quota, now_ms: i64) -> Self {
+        Self {
+            tokens: quota.capacity as f64,
+            updated_ms: now_ms,
+        }
+    }
+
+    // Refill for the time elapsed, then take a token when one is left.
+    pub fn take(&mut self, quota: &Quota, now_ms: i64) -> Bucket {
+        self.refill(quota, now_ms);
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        Bucket {
+            allowed,
+            tokens: self.tokens,
+        }
+    }
+
+    // A full bucket can be forgotten, it would start full again.
+    pub fn is_full(&self, quota: &Quota, now_ms: i64) -> bool {
+        let mut bucket = *self;
+        bucket.refill(quota, now_ms);
+        bucket.tokens >= quota.capacity as f64
+    }
+
+    fn refill(&mut self, quota: &Quota, now_ms: i64) {
+        let elapsed_ms = (now_ms - self.updated_ms).max(0) as f64;
+        self.tokens = (self.tokens + elapsed_ms * quota.refill_per_ms()).min(quota.capacity as f64);
+        self.updated_ms = now_ms;
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+// Number of tasks sharing one status, tag, assignee or creation day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatCount {
+    pub key: String,
+    pub count: i64,
+}
+
+// Task counts as read from the repository, for the tasks created in a date range.
+// Counts are sorted by count then key, days by date. Days without tasks are left out.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TaskStats {
+    pub by_status: Vec<StatCount>,
+    pub by_tag: Vec<StatCount>,
+    pub by_assignee: Vec<StatCount>,
+    pub created_per_day: Vec<StatCount>,
+}
+
+// Response of `GET /api/tasks/stats`, with every day of the range in `created_per_day`.
+#[derive(Debug, Serialize)]
+pub struct TaskStatsResponse {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub stats: TaskStats,
+}
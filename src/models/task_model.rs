@@ -1,18 +1,157 @@
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+use crate::models::patch_model::{non_null, nullable};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct Task {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
     #[validate(length(min = 5, message = "Title must have minimum of 5 characters"))]
     pub title: String,
     #[validate(length(min = 10, message = "Body must have aleast 10 characters"))]
-    pub body: String
+    pub body: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Id of the user working on the task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    // Set when the task is created. RFC 3339 in UTC, so it compares and groups by day as a string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    // Bumped on every write and exposed as the ETag.
+    #[serde(default)]
+    pub version: i64,
+}
+
+// A task as one row of an export or import, the same columns in CSV and NDJSON.
+// Tags are joined with `;` to fit in one CSV field. Missing columns get the task defaults.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskRecord {
+    #[serde(default)]
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default, serialize_with = "join_tags", deserialize_with = "split_tags")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl From<Task> for TaskRecord {
+    fn from(task: Task) -> Self {
+        Self {
+            id: task.id.unwrap_or_default(),
+            title: task.title,
+            body: task.body,
+            status: task.status,
+            tags: task.tags,
+            assignee: task.assignee,
+            created_at: task.created_at,
+        }
+    }
+}
+
+impl TaskRecord {
+    // New task of an imported row. The id is not kept, the creation time is, in UTC.
+    pub fn into_task(self) -> Result<Task, String> {
+        let created_at = match self.created_at.filter(|created_at| !created_at.is_empty()) {
+            Some(created_at) => Some(
+                DateTime::parse_from_rfc3339(&created_at)
+                    .map_err(|_| format!("created_at {} is not an RFC 3339 date", created_at))?
+                    .with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            ),
+            None => None,
+        };
+        Ok(Task {
+            id: None,
+            title: self.title,
+            body: self.body,
+            status: self.status,
+            tags: self.tags,
+            assignee: self.assignee.filter(|assignee| !assignee.is_empty()),
+            created_at,
+            version: 0,
+        })
+    }
+}
+
+fn join_tags<S: Serializer>(tags: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&tags.join(";"))
+}
+
+// Joined tags, or a list of tags as written by hand in NDJSON.
+fn split_tags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        Joined(String),
+        List(Vec<String>),
+    }
+    Ok(match Tags::deserialize(deserializer)? {
+        Tags::Joined(tags) => tags
+            .split(';')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        Tags::List(tags) => tags,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    #[default]
+    Todo,
+    InProgress,
+    Done,
+}
+
+// JSON merge patch for a task. Only the supplied fields are validated and updated.
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct TaskPatch {
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 5, message = "Title must have minimum of 5 characters"))]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 10, message = "Body must have aleast 10 characters"))]
+    pub body: Option<String>,
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    // `null` removes the assignee.
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<Option<String>>,
 }
 
-#[derive(Serialize, Deserialize)]
- pub struct TaskAggregate {
-    pub status: String,
-    pub count:  i64,
- }
\ No newline at end of file
+impl TaskPatch {
+    // Patch writing every field of a full task, for `PUT` updates. A missing assignee is removed.
+    pub fn replace(task: Task) -> Self {
+        Self {
+            title: Some(task.title),
+            body: Some(task.body),
+            status: Some(task.status),
+            tags: Some(task.tags),
+            assignee: Some(task.assignee),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.body.is_none()
+            && self.status.is_none()
+            && self.tags.is_none()
+            && self.assignee.is_none()
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+// A soft deleted task or user, as listed by the trash.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub id: String,
+    // Either "task" or "user".
+    pub kind: String,
+    // Task title or user name.
+    pub name: String,
+    pub deleted_at: String,
+}
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+use crate::models::patch_model::non_null;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
@@ -14,4 +16,31 @@ pub struct User {
     ))]
     pub location: String,
     pub title: String,
+    // Bumped on every write and exposed as the ETag.
+    #[serde(default)]
+    pub version: i64,
+}
+
+// JSON merge patch for a user. Only the supplied fields are validated and updated.
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UserPatch {
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 2, message = "Name must be have minimum of 3 characters"))]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    #[validate(length(
+        min = 2,
+        max = 15,
+        message = "Location character length between 2 and 15"
+    ))]
+    pub location: Option<String>,
+    #[serde(default, deserialize_with = "non_null", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl UserPatch {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.location.is_none() && self.title.is_none()
+    }
 }
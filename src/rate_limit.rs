@@ -0,0 +1,269 @@
+use std::future::{ready, Future, Ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::http::Method;
+use actix_web::{Error, HttpMessage, ResponseError};
+use derive_more::{Display, Error};
+use log::warn;
+
+use crate::auth::claims::Claims;
+use crate::config::settings::RateLimitSettings;
+use crate::metrics;
+use crate::models::error_model::ApiErrorType;
+use crate::models::rate_limit_model::{Bucket, Quota};
+use crate::repository::rate_limit_repo::RateLimitRepository;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "not an address or a CIDR range")]
+pub struct InvalidIpRange;
+
+// An address like `10.0.0.7`, or a CIDR range like `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for IpRange {
+    type Err = InvalidIpRange;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match range.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (range, None),
+        };
+        let network = address.parse::<IpAddr>().map_err(|_| InvalidIpRange)?.to_canonical();
+        let width = bits(network).1;
+        let prefix = match prefix {
+            None => width,
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= width).ok_or(InvalidIpRange)?,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, width) = bits(self.network);
+        let (ip, ip_width) = bits(ip.to_canonical());
+        if width != ip_width {
+            return false;
+        }
+        let host_bits = u32::from(width - self.prefix);
+        network.checked_shr(host_bits).unwrap_or(0) == ip.checked_shr(host_bits).unwrap_or(0)
+    }
+}
+
+// Address as an integer, with its width in bits.
+fn bits(ip: IpAddr) -> (u128, u8) {
+    match ip {
+        IpAddr::V4(ip) => (u32::from(ip).into(), 32),
+        IpAddr::V6(ip) => (ip.into(), 128),
+    }
+}
+
+struct Rule {
+    route: String,
+    methods: Vec<Method>,
+    by_account: bool,
+    quota: Quota,
+}
+
+impl Rule {
+    fn matches(&self, method: &Method, pattern: &str) -> bool {
+        let route = match self.route.strip_suffix('*') {
+            Some(prefix) => pattern.starts_with(prefix),
+            None => pattern == self.route,
+        };
+        route && (self.methods.is_empty() || self.methods.contains(method))
+    }
+}
+
+struct Limiter {
+    rules: Vec<Rule>,
+    trusted_proxies: Vec<IpRange>,
+    store: Arc<dyn RateLimitRepository>,
+}
+
+impl Limiter {
+    // Bucket of the client for one rule. Account rules fall back to the IP without a token.
+    fn key(&self, rule: &Rule, req: &ServiceRequest) -> String {
+        let account = if rule.by_account {
+            req.extensions().get::<Claims>().map(|claims| format!("account:{}", claims.sub))
+        } else {
+            None
+        };
+        let client = account.unwrap_or_else(|| match client_ip(req, &self.trusted_proxies) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_owned(),
+        });
+        format!("{} {}", rule.route, client)
+    }
+}
+
+// The peer, or behind trusted proxies the last `X-Forwarded-For` address they did not add.
+fn client_ip(req: &ServiceRequest, trusted_proxies: &[IpRange]) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|range| range.contains(ip));
+    let mut client = req.peer_addr()?.ip().to_canonical();
+    if !is_trusted(client) {
+        return Some(client);
+    }
+    let hops: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for hop in hops.iter().rev() {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = hop.to_canonical();
+        if !is_trusted(client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+// `RateLimit-*` headers of the bucket closest to running out.
+fn set_headers(headers: &mut HeaderMap, quota: &Quota, bucket: &Bucket) {
+    headers.insert(RATELIMIT_LIMIT, quota.capacity.into());
+    headers.insert(RATELIMIT_REMAINING, bucket.remaining().into());
+    headers.insert(RATELIMIT_RESET, bucket.reset_secs(quota).into());
+    let policy = format!("{};w={}", quota.capacity, quota.period_secs);
+    headers.insert(RATELIMIT_POLICY, HeaderValue::from_str(&policy).unwrap());
+}
+
+// Middleware taking a token from every rule matching the route pattern, 429 once a bucket is empty.
+// Account rules need the claims, so it goes inside the authentication of a scope.
+// The buckets are in the store, shared by every scope and worker it wraps.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimit {
+    pub fn new(settings: &RateLimitSettings, store: Arc<dyn RateLimitRepository>) -> Self {
+        // Settings are validated at startup, nothing is dropped by the parsing here.
+        let rules = if settings.enabled {
+            settings
+                .rules
+                .iter()
+                .map(|rule| Rule {
+                    route: rule.route.clone(),
+                    methods: rule
+                        .methods
+                        .iter()
+                        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+                        .collect(),
+                    by_account: rule.key == "account",
+                    quota: Quota {
+                        capacity: rule.requests,
+                        period_secs: rule.period_secs,
+                    },
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let trusted_proxies = settings
+            .trusted_proxies
+            .iter()
+            .filter_map(|proxy| proxy.parse().ok())
+            .collect();
+        Self {
+            limiter: Arc::new(Limiter {
+                rules,
+                trusted_proxies,
+                store,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: Arc<Limiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiter = self.limiter.clone();
+        Box::pin(async move {
+            let route = req.match_pattern().unwrap_or_default();
+            let mut tightest: Option<(Quota, Bucket)> = None;
+            for rule in limiter.rules.iter().filter(|rule| rule.matches(req.method(), &route)) {
+                let key = limiter.key(rule, &req);
+                // Requests go through while the store is down, rather than failing them all.
+                let bucket = match limiter.store.take(&key, &rule.quota).await {
+                    Ok(bucket) => bucket,
+                    Err(err) => {
+                        warn!("Rate limit store unavailable, {} not limited : {}", key, err);
+                        continue;
+                    }
+                };
+                if !bucket.allowed {
+                    warn!("Rate limit exceeded for {}", key);
+                    metrics::RATE_LIMITED_REQUESTS.with_label_values(&[&rule.route]).inc();
+                    let mut resp = ApiErrorType::TooManyRequests.error_response();
+                    set_headers(resp.headers_mut(), &rule.quota, &bucket);
+                    resp.headers_mut()
+                        .insert(RETRY_AFTER, bucket.retry_after_secs(&rule.quota).into());
+                    return Ok(req.into_response(resp).map_into_right_body());
+                }
+                if tightest.is_none_or(|(_, tight)| bucket.remaining() < tight.remaining()) {
+                    tightest = Some((rule.quota, bucket));
+                }
+            }
+
+            let mut res = service.call(req).await?;
+            if let Some((quota, bucket)) = tightest {
+                set_headers(res.headers_mut(), &quota, &bucket);
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
@@ -1,43 +1,79 @@
-use actix_web::web::Data;
+use async_trait::async_trait;
 use bson::doc;
-use mongodb::results::InsertOneResult;
-use mongodb::{error::Error, Client, Collection};
-
-use crate::{constants, models::auth_model::Auth};
-
-// Add a user to auth table with hash password.
-pub async fn auth_register(
-    client: &Data<Client>,
-    register_user: Auth,
-) -> Result<InsertOneResult, Error> {
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_AUTH_COLLECTION);
-    collection.insert_one(register_user, None).await
+use mongodb::{
+    error::{Error, ErrorKind, WriteFailure},
+    Collection,
+};
+
+use crate::config::state::AppState;
+use crate::metrics;
+use crate::models::auth_model::Auth;
+use crate::telemetry;
+
+// Mongo error code of a unique index violation.
+const DUPLICATE_KEY: i32 = 11000;
+
+// Storage of registered users and their credentials.
+#[async_trait]
+pub trait AuthRepository: Send + Sync {
+    // Add a user to auth table with hash password.
+    // Returns false when the email is already registered.
+    async fn auth_register(&self, register_user: Auth) -> Result<bool, Error>;
+
+    // Check if user with email already esitst in auth table or not.
+    // Returns true when the email is still free.
+    async fn check_email(&self, email: &str) -> Result<bool, Error>;
+
+    // Fetch user from auth table based on email id for authentication with credentials.
+    async fn fetch_by_email(&self, email: &str) -> Result<Option<Auth>, Error>;
+}
+
+// Auth repository on the MongoDB auth collection.
+pub struct MongoAuthRepository {
+    collection: Collection<Auth>,
 }
 
-// Check if user with email already esitst in auth table or not.
-pub async fn check_email(client: &Data<Client>, email: &String) -> bool {
-    let collection: Collection<Auth> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_AUTH_COLLECTION);
-    let count = collection
-        .count_documents(doc! {"email": email}, None)
-        .await;
-    match count {
-        Ok(c) => c == 0,
-        Err(_) => true,
+impl MongoAuthRepository {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            collection: state.auth.clone(),
+        }
     }
 }
 
-// Fetch user from auth table based on email id for authentication with credentials.
-pub async fn fetch_by_email(client: &Data<Client>, email: &String) -> Option<Auth> {
-    let collection: Collection<Auth> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_AUTH_COLLECTION);
-    let auth = collection.find_one(doc! {"email": email}, None).await;
-    match auth {
-        Ok(a) => a,
-        Err(_) => None,
+#[async_trait]
+impl AuthRepository for MongoAuthRepository {
+    // The unique email index rejects a duplicate that raced past `check_email`.
+    async fn auth_register(&self, register_user: Auth) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("auth_repo", "auth_register");
+        let _span = telemetry::span("auth_repo", "auth_register");
+        match self.collection.insert_one(register_user, None).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_duplicate_key(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
     }
+
+    async fn check_email(&self, email: &str) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("auth_repo", "check_email");
+        let _span = telemetry::span("auth_repo", "check_email");
+        let count = self
+            .collection
+            .count_documents(doc! {"email": email}, None)
+            .await?;
+        Ok(count == 0)
+    }
+
+    async fn fetch_by_email(&self, email: &str) -> Result<Option<Auth>, Error> {
+        let _timer = metrics::mongo_timer("auth_repo", "fetch_by_email");
+        let _span = telemetry::span("auth_repo", "fetch_by_email");
+        self.collection.find_one(doc! {"email": email}, None).await
+    }
+}
+
+fn is_duplicate_key(err: &Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) if write_error.code == DUPLICATE_KEY
+    )
 }
@@ -0,0 +1,573 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use async_trait::async_trait;
+use chrono::SecondsFormat;
+use futures::stream;
+use mongodb::{bson::DateTime, error::Error};
+use nanoid::nanoid;
+
+use crate::models::auth_model::Auth;
+use crate::models::cursor_model::Cursor;
+use crate::models::task_list_response::Tasks;
+use crate::models::stats_model::{StatCount, TaskStats};
+use crate::models::task_model::{Task, TaskPatch};
+use crate::models::trash_model::TrashItem;
+use crate::models::user_list_response::Users;
+use crate::models::user_model::{User, UserPatch};
+use crate::repository::auth_repo::AuthRepository;
+use crate::repository::task_repo::{
+    new_task_doc, split_creates, TaskRepository, TaskStream, TaskWrite, WriteOutcome,
+};
+use crate::repository::trash_repo::TrashRepository;
+use crate::repository::user_repo::{UserRepository, UserStream};
+
+// A stored document with its trash timestamp, the `deleted_at` field in Mongo.
+#[derive(Clone)]
+struct Stored<T> {
+    doc: T,
+    deleted_at: Option<DateTime>,
+}
+
+#[derive(Default)]
+struct Store {
+    tasks: Vec<Stored<Task>>,
+    users: Vec<Stored<User>>,
+    auth: Vec<Auth>,
+}
+
+// Every data repository kept in process memory, for tests without MongoDB.
+// Follows the Mongo backend for ids, versions, the trash and sort orders.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    store: Mutex<Store>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn version_matches(version: i64, versions: Option<&[i64]>) -> bool {
+    versions.is_none_or(|versions| versions.contains(&version))
+}
+
+// Keyset page of rows already sorted ascending by `key`.
+// Ascending after the cursor, descending before it.
+fn keyset_page<T>(
+    rows: Vec<T>,
+    key: impl Fn(&T) -> (&str, &str),
+    cursor: Option<&Cursor>,
+    backward: bool,
+    limit: i64,
+) -> Vec<T> {
+    let mut rows: Vec<T> = rows
+        .into_iter()
+        .filter(|row| match cursor {
+            Some(c) if backward => key(row) < (c.key.as_str(), c.id.as_str()),
+            Some(c) => key(row) > (c.key.as_str(), c.id.as_str()),
+            None => true,
+        })
+        .collect();
+    if backward {
+        rows.reverse();
+    }
+    rows.truncate(limit.max(0) as usize);
+    rows
+}
+
+impl Store {
+    fn live_task(&mut self, id: &str, versions: Option<&[i64]>) -> Option<&mut Task> {
+        self.tasks
+            .iter_mut()
+            .filter(|task| task.deleted_at.is_none())
+            .map(|task| &mut task.doc)
+            .find(|task| task.id.as_deref() == Some(id) && version_matches(task.version, versions))
+    }
+
+    // Live tasks sorted by (title, id).
+    fn sorted_tasks(&self) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|task| task.deleted_at.is_none())
+            .map(|task| task.doc.clone())
+            .collect();
+        tasks.sort_by(|a, b| (&a.title, &a.id).cmp(&(&b.title, &b.id)));
+        tasks
+    }
+
+    fn patch_task(&mut self, id: &str, patch: &TaskPatch, versions: Option<&[i64]>) -> bool {
+        match self.live_task(id, versions) {
+            Some(task) => {
+                if let Some(title) = &patch.title {
+                    task.title = title.clone();
+                }
+                if let Some(body) = &patch.body {
+                    task.body = body.clone();
+                }
+                if let Some(status) = patch.status {
+                    task.status = status;
+                }
+                if let Some(tags) = &patch.tags {
+                    task.tags = tags.clone();
+                }
+                if let Some(assignee) = &patch.assignee {
+                    task.assignee = assignee.clone();
+                }
+                task.version += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn trash_task(&mut self, id: &str, versions: Option<&[i64]>) -> bool {
+        let task = self.tasks.iter_mut().find(|task| {
+            task.deleted_at.is_none()
+                && task.doc.id.as_deref() == Some(id)
+                && version_matches(task.doc.version, versions)
+        });
+        match task {
+            Some(task) => {
+                task.deleted_at = Some(DateTime::now());
+                task.doc.version += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Run an update or a delete of a bulk request, returns whether a task matched.
+    fn write_task(&mut self, write: TaskWrite) -> bool {
+        match write {
+            TaskWrite::Update { id, patch, versions } => {
+                self.patch_task(&id, &patch, versions.as_deref())
+            }
+            TaskWrite::Delete { id, versions } => self.trash_task(&id, versions.as_deref()),
+            TaskWrite::Create(_) => false,
+        }
+    }
+
+    fn live_user(&mut self, id: &str, versions: Option<&[i64]>) -> Option<&mut Stored<User>> {
+        self.users.iter_mut().find(|user| {
+            user.deleted_at.is_none()
+                && user.doc.id.as_deref() == Some(id)
+                && version_matches(user.doc.version, versions)
+        })
+    }
+
+    // Live users sorted by (name, id).
+    fn sorted_users(&self) -> Vec<User> {
+        let mut users: Vec<User> = self
+            .users
+            .iter()
+            .filter(|user| user.deleted_at.is_none())
+            .map(|user| user.doc.clone())
+            .collect();
+        users.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+        users
+    }
+}
+
+fn to_tasks(task: Task) -> Tasks {
+    Tasks {
+        id: task.id.unwrap_or_default(),
+        title: task.title,
+        body: task.body,
+    }
+}
+
+fn to_users(user: User) -> Users {
+    Users {
+        id: user.id.unwrap_or_default(),
+        name: user.name,
+        location: user.location,
+        title: user.title,
+    }
+}
+
+// Counts sorted like the Mongo pipeline, largest count first then by key.
+fn largest_first(counts: BTreeMap<String, i64>) -> Vec<StatCount> {
+    let mut counts: Vec<StatCount> = counts
+        .into_iter()
+        .map(|(key, count)| StatCount { key, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    counts
+}
+
+fn task_key(task: &Task) -> (&str, &str) {
+    (task.title.as_str(), task.id.as_deref().unwrap_or_default())
+}
+
+fn user_key(user: &User) -> (&str, &str) {
+    (user.name.as_str(), user.id.as_deref().unwrap_or_default())
+}
+
+#[async_trait]
+impl TaskRepository for InMemoryRepository {
+    async fn create_task(&self, new_task: Task) -> Result<Option<Task>, Error> {
+        let new_doc = new_task_doc(new_task);
+        self.store().tasks.push(Stored {
+            doc: new_doc.clone(),
+            deleted_at: None,
+        });
+        Ok(Some(new_doc))
+    }
+
+    async fn get_task(&self, id: &str) -> Result<Option<Task>, Error> {
+        Ok(self.store().live_task(id, None).map(|task| task.clone()))
+    }
+
+    async fn update_task(
+        &self,
+        id: &str,
+        patch: &TaskPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error> {
+        Ok(self.store().patch_task(id, patch, versions))
+    }
+
+    async fn delete_task(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error> {
+        Ok(self.store().trash_task(id, versions))
+    }
+
+    async fn restore_task(&self, id: &str) -> Result<bool, Error> {
+        let mut store = self.store();
+        let task = store
+            .tasks
+            .iter_mut()
+            .find(|task| task.deleted_at.is_some() && task.doc.id.as_deref() == Some(id));
+        match task {
+            Some(task) => {
+                task.deleted_at = None;
+                task.doc.version += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_all_tasks(&self, offset: u64, limit: i64) -> Result<Vec<Tasks>, Error> {
+        let tasks = self.store().sorted_tasks();
+        Ok(tasks
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit.max(0) as usize)
+            .map(to_tasks)
+            .collect())
+    }
+
+    async fn get_tasks_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Tasks>, Error> {
+        let tasks = self.store().sorted_tasks();
+        let page = keyset_page(tasks, task_key, cursor, backward, limit);
+        Ok(page.into_iter().map(to_tasks).collect())
+    }
+
+    async fn get_tasks_size(&self) -> Result<u64, Error> {
+        Ok(self.store().sorted_tasks().len() as u64)
+    }
+
+    async fn export_tasks(&self) -> Result<TaskStream, Error> {
+        let tasks = self.store().sorted_tasks();
+        Ok(Box::pin(stream::iter(tasks.into_iter().map(Ok))))
+    }
+
+    async fn bulk_write(&self, writes: Vec<TaskWrite>) -> Vec<WriteOutcome> {
+        let mut outcomes: Vec<WriteOutcome> = writes.iter().map(|_| WriteOutcome::Aborted).collect();
+        let (creates, others) = split_creates(writes);
+        let mut store = self.store();
+        for (index, task) in creates {
+            outcomes[index] = WriteOutcome::Created(task.id.clone().unwrap_or_default());
+            store.tasks.push(Stored {
+                doc: task,
+                deleted_at: None,
+            });
+        }
+        for (index, write) in others {
+            outcomes[index] = if store.write_task(write) {
+                WriteOutcome::Written
+            } else {
+                WriteOutcome::NotMatched
+            };
+        }
+        outcomes
+    }
+
+    // Writes go to a copy of the tasks, which replaces them only when every write matched.
+    async fn bulk_write_atomic(&self, writes: Vec<TaskWrite>) -> Result<Vec<WriteOutcome>, Error> {
+        let mut outcomes: Vec<WriteOutcome> = writes.iter().map(|_| WriteOutcome::Aborted).collect();
+        let (creates, others) = split_creates(writes);
+        let mut store = self.store();
+        let committed = std::mem::take(&mut store.tasks);
+        store.tasks = committed.clone();
+
+        for (_, task) in &creates {
+            store.tasks.push(Stored {
+                doc: task.clone(),
+                deleted_at: None,
+            });
+        }
+        for (index, write) in others {
+            if !store.write_task(write) {
+                store.tasks = committed;
+                outcomes[index] = WriteOutcome::NotMatched;
+                return Ok(outcomes);
+            }
+        }
+
+        for (index, task) in creates {
+            outcomes[index] = WriteOutcome::Created(task.id.unwrap_or_default());
+        }
+        for outcome in outcomes.iter_mut() {
+            if let WriteOutcome::Aborted = outcome {
+                *outcome = WriteOutcome::Written;
+            }
+        }
+        Ok(outcomes)
+    }
+
+    async fn task_stats(&self, from: &str, to: &str) -> Result<TaskStats, Error> {
+        let tasks: Vec<Task> = self
+            .store()
+            .sorted_tasks()
+            .into_iter()
+            .filter(|task| {
+                task.created_at
+                    .as_deref()
+                    .is_some_and(|created_at| from <= created_at && created_at < to)
+            })
+            .collect();
+
+        let mut by_status = BTreeMap::new();
+        let mut by_tag = BTreeMap::new();
+        let mut by_assignee = BTreeMap::new();
+        let mut created_per_day = BTreeMap::new();
+        for task in &tasks {
+            let status = bson::to_bson(&task.status)?.as_str().unwrap_or_default().to_owned();
+            *by_status.entry(status).or_insert(0) += 1;
+            for tag in &task.tags {
+                *by_tag.entry(tag.clone()).or_insert(0) += 1;
+            }
+            if let Some(assignee) = &task.assignee {
+                *by_assignee.entry(assignee.clone()).or_insert(0) += 1;
+            }
+            let day = task.created_at.as_deref().unwrap_or_default();
+            *created_per_day.entry(day.get(..10).unwrap_or(day).to_owned()).or_insert(0) += 1;
+        }
+
+        Ok(TaskStats {
+            by_status: largest_first(by_status),
+            by_tag: largest_first(by_tag),
+            by_assignee: largest_first(by_assignee),
+            created_per_day: created_per_day
+                .into_iter()
+                .map(|(key, count)| StatCount { key, count })
+                .collect(),
+        })
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn create_user(&self, new_user: User) -> Result<Option<User>, Error> {
+        let new_doc = User {
+            id: Some(nanoid!()),
+            version: 1,
+            ..new_user
+        };
+        self.store().users.push(Stored {
+            doc: new_doc.clone(),
+            deleted_at: None,
+        });
+        Ok(Some(new_doc))
+    }
+
+    async fn create_users(&self, new_users: Vec<User>) -> Vec<Result<String, Error>> {
+        let mut store = self.store();
+        new_users
+            .into_iter()
+            .map(|user| {
+                let id = nanoid!();
+                store.users.push(Stored {
+                    doc: User {
+                        id: Some(id.clone()),
+                        version: 1,
+                        ..user
+                    },
+                    deleted_at: None,
+                });
+                Ok(id)
+            })
+            .collect()
+    }
+
+    async fn get_user(&self, id: &str) -> Result<Option<User>, Error> {
+        Ok(self.store().live_user(id, None).map(|user| user.doc.clone()))
+    }
+
+    async fn update_user(
+        &self,
+        id: &str,
+        patch: &UserPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error> {
+        let mut store = self.store();
+        match store.live_user(id, versions) {
+            Some(user) => {
+                if let Some(name) = &patch.name {
+                    user.doc.name = name.clone();
+                }
+                if let Some(location) = &patch.location {
+                    user.doc.location = location.clone();
+                }
+                if let Some(title) = &patch.title {
+                    user.doc.title = title.clone();
+                }
+                user.doc.version += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_user(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error> {
+        let mut store = self.store();
+        match store.live_user(id, versions) {
+            Some(user) => {
+                user.deleted_at = Some(DateTime::now());
+                user.doc.version += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn restore_user(&self, id: &str) -> Result<bool, Error> {
+        let mut store = self.store();
+        let user = store
+            .users
+            .iter_mut()
+            .find(|user| user.deleted_at.is_some() && user.doc.id.as_deref() == Some(id));
+        match user {
+            Some(user) => {
+                user.deleted_at = None;
+                user.doc.version += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_all_users(&self, offset: u64, limit: i64) -> Result<Vec<Users>, Error> {
+        let users = self.store().sorted_users();
+        Ok(users
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit.max(0) as usize)
+            .map(to_users)
+            .collect())
+    }
+
+    async fn get_users_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Users>, Error> {
+        let users = self.store().sorted_users();
+        let page = keyset_page(users, user_key, cursor, backward, limit);
+        Ok(page.into_iter().map(to_users).collect())
+    }
+
+    async fn get_users_size(&self) -> Result<u64, Error> {
+        Ok(self.store().sorted_users().len() as u64)
+    }
+
+    async fn export_users(&self) -> Result<UserStream, Error> {
+        let users = self.store().sorted_users();
+        Ok(Box::pin(stream::iter(users.into_iter().map(Ok))))
+    }
+}
+
+#[async_trait]
+impl AuthRepository for InMemoryRepository {
+    async fn auth_register(&self, register_user: Auth) -> Result<bool, Error> {
+        let mut store = self.store();
+        if store.auth.iter().any(|auth| auth.email == register_user.email) {
+            return Ok(false);
+        }
+        store.auth.push(register_user);
+        Ok(true)
+    }
+
+    async fn check_email(&self, email: &str) -> Result<bool, Error> {
+        Ok(!self.store().auth.iter().any(|auth| auth.email == email))
+    }
+
+    async fn fetch_by_email(&self, email: &str) -> Result<Option<Auth>, Error> {
+        Ok(self
+            .store()
+            .auth
+            .iter()
+            .find(|auth| auth.email == email)
+            .cloned())
+    }
+}
+
+#[async_trait]
+impl TrashRepository for InMemoryRepository {
+    async fn get_trash(&self, offset: u64, limit: i64) -> Result<Vec<TrashItem>, Error> {
+        let store = self.store();
+        let tasks = store.tasks.iter().filter_map(|task| {
+            task.deleted_at
+                .map(|deleted_at| (task.doc.id.clone(), "task", task.doc.title.clone(), deleted_at))
+        });
+        let users = store.users.iter().filter_map(|user| {
+            user.deleted_at
+                .map(|deleted_at| (user.doc.id.clone(), "user", user.doc.name.clone(), deleted_at))
+        });
+        let mut items: Vec<_> = tasks.chain(users).collect();
+        items.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0)));
+        Ok(items
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit.max(0) as usize)
+            .map(|(id, kind, name, deleted_at)| TrashItem {
+                id: id.unwrap_or_default(),
+                kind: kind.to_string(),
+                name,
+                deleted_at: deleted_at
+                    .to_chrono()
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+            })
+            .collect())
+    }
+
+    async fn get_trash_size(&self) -> Result<u64, Error> {
+        let store = self.store();
+        let tasks = store.tasks.iter().filter(|task| task.deleted_at.is_some()).count();
+        let users = store.users.iter().filter(|user| user.deleted_at.is_some()).count();
+        Ok((tasks + users) as u64)
+    }
+
+    async fn purge_trash(&self, cutoff: DateTime) -> Result<u64, Error> {
+        let mut store = self.store();
+        let before = store.tasks.len() + store.users.len();
+        let expired = |deleted_at: Option<DateTime>| deleted_at.is_some_and(|at| at < cutoff);
+        store.tasks.retain(|task| !expired(task.deleted_at));
+        store.users.retain(|user| !expired(user.deleted_at));
+        Ok((before - store.tasks.len() - store.users.len()) as u64)
+    }
+}
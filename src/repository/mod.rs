@@ -1,3 +1,6 @@
 pub mod auth_repo;
+pub mod memory_repo;
+pub mod rate_limit_repo;
 pub mod user_repo;
-pub mod task_repo;
\ No newline at end of file
+pub mod task_repo;
+pub mod trash_repo;
\ No newline at end of file
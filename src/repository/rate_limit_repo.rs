@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::{
+    bson::{doc, Document},
+    error::Error,
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    Collection,
+};
+
+use crate::config::state::AppState;
+use crate::metrics;
+use crate::models::rate_limit_model::{Bucket, Quota, TokenBucket};
+use crate::telemetry;
+
+// Token buckets of the rate limiter, one per rule and client.
+#[async_trait]
+pub trait RateLimitRepository: Send + Sync {
+    // Refill the bucket of `key` for the time elapsed, then take a token when one is left.
+    // A bucket seen for the first time starts full.
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Bucket, Error>;
+}
+
+// Buckets of this process only, each replica limits on its own.
+#[derive(Default)]
+pub struct MemoryRateLimitRepository {
+    buckets: Mutex<HashMap<String, (TokenBucket, Quota)>>,
+}
+
+// Full buckets are dropped past this many, so idle clients do not pile up.
+const MAX_BUCKETS: usize = 10_000;
+
+impl MemoryRateLimitRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitRepository for MemoryRateLimitRepository {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Bucket, Error> {
+        let now_ms = Utc::now().timestamp_millis();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            buckets.retain(|_, (bucket, quota)| !bucket.is_full(quota, now_ms));
+        }
+        let (bucket, _) = buckets
+            .entry(key.to_owned())
+            .or_insert_with(|| (TokenBucket::full(quota, now_ms), *quota));
+        Ok(bucket.take(quota, now_ms))
+    }
+}
+
+// Buckets shared by every replica, refilled on the database clock.
+// Expired buckets would be full again, a TTL index on `expires_at` removes them.
+pub struct MongoRateLimitRepository {
+    buckets: Collection<Document>,
+}
+
+impl MongoRateLimitRepository {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            buckets: state.rate_limits.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitRepository for MongoRateLimitRepository {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Bucket, Error> {
+        let _timer = metrics::mongo_timer("rate_limit_repo", "take");
+        let _span = telemetry::span("rate_limit_repo", "take");
+        let capacity = quota.capacity as f64;
+        // One atomic update: refill since `updated_at`, then take a token when there is one.
+        let pipeline = vec![
+            doc! {
+                "$set": {
+                    "tokens": {
+                        "$min": [
+                            capacity,
+                            {
+                                "$add": [
+                                    { "$ifNull": ["$tokens", capacity] },
+                                    {
+                                        "$multiply": [
+                                            { "$subtract": ["$$NOW", { "$ifNull": ["$updated_at", "$$NOW"] }] },
+                                            quota.refill_per_ms(),
+                                        ]
+                                    },
+                                ]
+                            },
+                        ]
+                    },
+                    "updated_at": "$$NOW",
+                }
+            },
+            doc! {
+                "$set": {
+                    "allowed": { "$gte": ["$tokens", 1.0] },
+                    "tokens": {
+                        "$cond": [{ "$gte": ["$tokens", 1.0] }, { "$subtract": ["$tokens", 1.0] }, "$tokens"]
+                    },
+                    "expires_at": { "$add": ["$$NOW", (quota.period_secs * 1000) as i64] },
+                }
+            },
+        ];
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        let bucket = self
+            .buckets
+            .find_one_and_update(doc! {"_id": key}, pipeline, options)
+            .await?
+            .unwrap_or_default();
+        Ok(Bucket {
+            allowed: bucket.get_bool("allowed").unwrap_or(true),
+            tokens: bucket.get_f64("tokens").unwrap_or_default(),
+        })
+    }
+}
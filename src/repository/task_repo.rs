@@ -1,94 +1,382 @@
-use actix_web::web::Data;
-use futures::TryStreamExt;
-use mongodb::options::FindOptions;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use chrono::SecondsFormat;
+use futures::{Stream, TryStreamExt};
+use mongodb::options::{FindOptions, InsertManyOptions};
 use mongodb::{
-    bson::doc, 
-    error::Error,
-    results::{DeleteResult, UpdateResult},
-    Client, Collection,
+    bson::{doc, DateTime, Document},
+    error::{Error, ErrorKind},
+    Client, ClientSession, Collection,
 };
 use nanoid::nanoid;
 
-use crate::models::task_model::Task;
-use crate::{constants, models::task_list_response::Tasks};
+use crate::config::state::AppState;
+use crate::metrics;
+use crate::models::cursor_model::Cursor;
+use crate::models::etag_model::version_filter;
+use crate::models::patch_model::to_update_doc;
+use crate::models::stats_model::TaskStats;
+use crate::models::task_model::{Task, TaskPatch};
+use crate::models::task_list_response::Tasks;
+use crate::telemetry;
+
+// Every task of an export, read one by one.
+pub type TaskStream = Pin<Box<dyn Stream<Item = Result<Task, Error>> + Send>>;
+
+// Task storage used by the task service.
+// Writes report whether a task matched instead of driver results, so any backend can implement it.
+#[async_trait]
+pub trait TaskRepository: Send + Sync {
+    // Create a task with a new unique id and return it as stored.
+    async fn create_task(&self, new_task: Task) -> Result<Option<Task>, Error>;
+
+    // Tasks in the trash are never returned.
+    async fn get_task(&self, id: &str) -> Result<Option<Task>, Error>;
+
+    // Apply the fields present in the patch and bump the version.
+    // When `versions` is given the task must currently be at one of them.
+    async fn update_task(
+        &self,
+        id: &str,
+        patch: &TaskPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error>;
+
+    // Move a task to the trash, the purge job removes it later.
+    async fn delete_task(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error>;
+
+    async fn restore_task(&self, id: &str) -> Result<bool, Error>;
+
+    // Tasks sorted by (title, id).
+    async fn get_all_tasks(&self, offset: u64, limit: i64) -> Result<Vec<Tasks>, Error>;
+
+    // Tasks with keyset pagination on (title, id).
+    // Rows come back in ascending order after the cursor, or in descending
+    // order before it, so the closest `limit` rows are always returned.
+    async fn get_tasks_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Tasks>, Error>;
+
+    async fn get_tasks_size(&self) -> Result<u64, Error>;
 
-// Function to create a new task in MongoDB
-pub async fn create_task(client: &Data<Client>, new_task: Task) -> Result<Option<Task>, Error> {
-    let new_doc = Task {
-        id: Some(nanoid!()),  // Generate a unique ID for the new task
-        title: new_task.title,
-        body: new_task.body,
-    };
+    // Every task, in the order of the list endpoint.
+    async fn export_tasks(&self) -> Result<TaskStream, Error>;
 
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    let result = collection.insert_one(new_doc.clone(), None).await?;
+    // Run the writes of a bulk request, each one on its own.
+    async fn bulk_write(&self, writes: Vec<TaskWrite>) -> Vec<WriteOutcome>;
 
-    collection.find_one(doc! { "_id": result.inserted_id }, None).await
+    // Run all writes of a bulk request at once.
+    // The first write that fails or matches nothing rolls back the batch,
+    // every other write is then reported as `Aborted`.
+    async fn bulk_write_atomic(&self, writes: Vec<TaskWrite>) -> Result<Vec<WriteOutcome>, Error>;
+
+    // Counts of the live tasks created from `from` (included) to `to` (excluded).
+    // Both are `YYYY-MM-DD` dates.
+    async fn task_stats(&self, from: &str, to: &str) -> Result<TaskStats, Error>;
 }
 
-// Function to retrieve a task by its ID from MongoDB
-pub async fn get_task(client: &Data<Client>, id: &String) -> Result<Option<Task>, Error> {
-    let filter = doc! { "_id": id };
-    
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    collection.find_one(filter, None).await
+// A validated write of a bulk request.
+pub enum TaskWrite {
+    Create(Task),
+    Update {
+        id: String,
+        patch: TaskPatch,
+        versions: Option<Vec<i64>>,
+    },
+    Delete {
+        id: String,
+        versions: Option<Vec<i64>>,
+    },
 }
 
-// Function to update a task by its ID
-pub async fn update_task(
-    client: &Data<Client>,
-    id: &String,
-    updated_task: Task,
-) -> Result<UpdateResult, Error> {
-    let filter = doc! { "_id": id };
-    let update_doc = doc! {
-        "$set": {
-            "title": updated_task.title,
-            "body": updated_task.body
-        },
-    };
-
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    collection.update_one(filter, update_doc, None).await
+pub enum WriteOutcome {
+    Created(String),
+    Written,
+    NotMatched,
+    Failed(Error),
+    // Rolled back or never run because another write of an atomic batch failed.
+    Aborted,
 }
 
-// Function to delete a task by its ID
-pub async fn delete_task(client: &Data<Client>, id: &String) -> Result<DeleteResult, Error> {
-    let filter = doc! { "_id": id };
+// Task repository on the MongoDB task collection.
+pub struct MongoTaskRepository {
+    client: Client,
+    collection: Collection<Task>,
+}
 
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    collection.delete_one(filter, None).await
+impl MongoTaskRepository {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            client: state.client.clone(),
+            collection: state.tasks.clone(),
+        }
+    }
 }
 
-// Function to retrieve all tasks with pagination
-pub async fn get_all_tasks(
-    client: &Data<Client>,
-    offset: u64,
-    limit: i64,
-) -> Result<Vec<Tasks>, Error> {
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    let find_options = FindOptions::builder()
-        .skip(offset)
-        .limit(limit)
-        .sort(doc! { "title": 1 })  // Sorting tasks by title
-        .build();
-    
-    let mut cursors = collection.find(None, find_options).await?;
+#[async_trait]
+impl TaskRepository for MongoTaskRepository {
+    async fn create_task(&self, new_task: Task) -> Result<Option<Task>, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "create_task");
+        let _span = telemetry::span("task_repo", "create_task");
+        let new_doc = new_task_doc(new_task);
+
+        let collection = &self.collection;
+
+        let result = collection.insert_one(new_doc.clone(), None).await?;
+
+        collection.find_one(doc! { "_id": result.inserted_id }, None).await
+    }
+
+    async fn get_task(&self, id: &str) -> Result<Option<Task>, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "get_task");
+        let _span = telemetry::span("task_repo", "get_task");
+        let filter = doc! { "_id": id, "deleted_at": null };
+
+        self.collection.find_one(filter, None).await
+    }
+
+    // Only the fields present in the patch are touched with `$set`/`$unset`.
+    async fn update_task(
+        &self,
+        id: &str,
+        patch: &TaskPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "update_task");
+        let _span = telemetry::span("task_repo", "update_task");
+        let filter = write_filter(id, versions);
+        let update_doc = patch_update_doc(patch)?;
+
+        let result = self.collection.update_one(filter, update_doc, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    // The task is moved to the trash by setting `deleted_at`.
+    async fn delete_task(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "delete_task");
+        let _span = telemetry::span("task_repo", "delete_task");
+        let filter = write_filter(id, versions);
+
+        let result = self.collection.update_one(filter, trash_update_doc(), None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn restore_task(&self, id: &str) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "restore_task");
+        let _span = telemetry::span("task_repo", "restore_task");
+        let filter = doc! { "_id": id, "deleted_at": { "$ne": null } };
+        let update_doc = doc! {
+            "$unset": { "deleted_at": "" },
+            "$inc": { "version": 1_i64 },
+        };
+
+        let result = self.collection.update_one(filter, update_doc, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn get_all_tasks(&self, offset: u64, limit: i64) -> Result<Vec<Tasks>, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "get_all_tasks");
+        let _span = telemetry::span("task_repo", "get_all_tasks");
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit)
+            .sort(doc! { "title": 1, "_id": 1 })  // Sorting tasks by title
+            .build();
+
+        let cursors = self
+            .collection
+            .find(doc! { "deleted_at": null }, find_options)
+            .await?;
+        collect_tasks(cursors).await
+    }
+
+    async fn get_tasks_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Tasks>, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "get_tasks_by_cursor");
+        let _span = telemetry::span("task_repo", "get_tasks_by_cursor");
+        let (op, direction) = if backward { ("$lt", -1) } else { ("$gt", 1) };
+        let mut filter = doc! { "deleted_at": null };
+        if let Some(c) = cursor {
+            filter.insert(
+                "$or",
+                vec![
+                    doc! { "title": { op: &c.key } },
+                    doc! { "title": &c.key, "_id": { op: &c.id } },
+                ],
+            );
+        }
+        let find_options = FindOptions::builder()
+            .limit(limit)
+            .sort(doc! { "title": direction, "_id": direction })
+            .build();
+
+        let cursors = self.collection.find(filter, find_options).await?;
+        collect_tasks(cursors).await
+    }
+
+    async fn get_tasks_size(&self) -> Result<u64, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "get_tasks_size");
+        let _span = telemetry::span("task_repo", "get_tasks_size");
+        self.collection
+            .count_documents(doc! { "deleted_at": null }, None)
+            .await
+    }
+
+    // Streams straight from the Mongo cursor.
+    async fn export_tasks(&self) -> Result<TaskStream, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "export_tasks");
+        let _span = telemetry::span("task_repo", "export_tasks");
+        let find_options = FindOptions::builder()
+            .sort(doc! { "title": 1, "_id": 1 })
+            .build();
+
+        let cursor = self
+            .collection
+            .find(doc! { "deleted_at": null }, find_options)
+            .await?;
+        Ok(Box::pin(cursor))
+    }
+
+    // Creates go in a single unordered `insert_many`, updates and deletes run in request order.
+    async fn bulk_write(&self, writes: Vec<TaskWrite>) -> Vec<WriteOutcome> {
+        let _timer = metrics::mongo_timer("task_repo", "bulk_write");
+        let _span = telemetry::span("task_repo", "bulk_write");
+        let collection = &self.collection;
+
+        let mut outcomes: Vec<WriteOutcome> = writes.iter().map(|_| WriteOutcome::Aborted).collect();
+        let (creates, others) = split_creates(writes);
+
+        if !creates.is_empty() {
+            let options = InsertManyOptions::builder().ordered(false).build();
+            let docs = creates.iter().map(|(_, task)| task);
+            match collection.insert_many(docs, options).await {
+                Ok(_) => {
+                    for (index, task) in &creates {
+                        outcomes[*index] = WriteOutcome::Created(task.id.clone().unwrap_or_default());
+                    }
+                }
+                Err(err) => {
+                    // Only the rejected documents failed, unless the whole call did.
+                    let failed: Option<Vec<usize>> = match err.kind.as_ref() {
+                        ErrorKind::BulkWrite(failure) => failure
+                            .write_errors
+                            .as_ref()
+                            .map(|errors| errors.iter().map(|e| e.index).collect()),
+                        _ => None,
+                    };
+                    for (position, (index, task)) in creates.iter().enumerate() {
+                        outcomes[*index] = match &failed {
+                            Some(failed) if !failed.contains(&position) => {
+                                WriteOutcome::Created(task.id.clone().unwrap_or_default())
+                            }
+                            _ => WriteOutcome::Failed(err.clone()),
+                        };
+                    }
+                }
+            }
+        }
+
+        for (index, write) in others {
+            outcomes[index] = match write_one(collection, write, None).await {
+                Ok(true) => WriteOutcome::Written,
+                Ok(false) => WriteOutcome::NotMatched,
+                Err(err) => WriteOutcome::Failed(err),
+            };
+        }
+        outcomes
+    }
+
+    // Runs in a Mongo transaction.
+    async fn bulk_write_atomic(&self, writes: Vec<TaskWrite>) -> Result<Vec<WriteOutcome>, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "bulk_write_atomic");
+        let _span = telemetry::span("task_repo", "bulk_write_atomic");
+        let collection = &self.collection;
+
+        let mut outcomes: Vec<WriteOutcome> = writes.iter().map(|_| WriteOutcome::Aborted).collect();
+        let (creates, others) = split_creates(writes);
+
+        let mut session = self.client.start_session(None).await?;
+        session.start_transaction(None).await?;
+
+        if !creates.is_empty() {
+            let docs = creates.iter().map(|(_, task)| task);
+            if let Err(err) = collection.insert_many_with_session(docs, None, &mut session).await {
+                session.abort_transaction().await?;
+                for (index, _) in &creates {
+                    outcomes[*index] = WriteOutcome::Failed(err.clone());
+                }
+                return Ok(outcomes);
+            }
+        }
+
+        for (index, write) in others {
+            let failure = match write_one(collection, write, Some(&mut session)).await {
+                Ok(true) => continue,
+                Ok(false) => WriteOutcome::NotMatched,
+                Err(err) => WriteOutcome::Failed(err),
+            };
+            session.abort_transaction().await?;
+            outcomes[index] = failure;
+            return Ok(outcomes);
+        }
+
+        session.commit_transaction().await?;
+        for (index, task) in creates {
+            outcomes[index] = WriteOutcome::Created(task.id.unwrap_or_default());
+        }
+        for outcome in outcomes.iter_mut() {
+            if let WriteOutcome::Aborted = outcome {
+                *outcome = WriteOutcome::Written;
+            }
+        }
+        Ok(outcomes)
+    }
+
+    // One `$facet` pipeline, so every count is read from the same tasks.
+    async fn task_stats(&self, from: &str, to: &str) -> Result<TaskStats, Error> {
+        let _timer = metrics::mongo_timer("task_repo", "task_stats");
+        let _span = telemetry::span("task_repo", "task_stats");
+        let mut by_tag = vec![doc! { "$unwind": "$tags" }];
+        by_tag.extend(count_stages("$tags"));
+        let mut by_assignee = vec![doc! { "$match": { "assignee": { "$type": "string" } } }];
+        by_assignee.extend(count_stages("$assignee"));
+        let created_per_day = vec![
+            doc! { "$group": {
+                "_id": { "$substrBytes": ["$created_at", 0, 10] },
+                "count": { "$sum": 1 },
+            } },
+            doc! { "$project": { "_id": 0, "key": "$_id", "count": 1 } },
+            doc! { "$sort": { "key": 1 } },
+        ];
+        let pipeline = vec![
+            doc! { "$match": { "deleted_at": null, "created_at": { "$gte": from, "$lt": to } } },
+            doc! {
+                "$facet": {
+                    "by_status": count_stages("$status"),
+                    "by_tag": by_tag,
+                    "by_assignee": by_assignee,
+                    "created_per_day": created_per_day,
+                }
+            },
+        ];
+
+        let mut cursor = self.collection.aggregate(pipeline, None).await?;
+        match cursor.try_next().await? {
+            Some(facets) => Ok(bson::from_document(facets)?),
+            None => Ok(TaskStats::default()),
+        }
+    }
+}
+
+async fn collect_tasks(mut cursors: mongodb::Cursor<Task>) -> Result<Vec<Tasks>, Error> {
     let mut tasks: Vec<Tasks> = Vec::new();
 
     while let Some(task) = cursors.try_next().await? {
@@ -98,16 +386,106 @@ pub async fn get_all_tasks(
             body: task.body,
         });
     }
-    
+
     Ok(tasks)
 }
 
-// Function to get the total count of tasks
-pub async fn get_tasks_size(client: &Data<Client>) -> Result<u64, Error> {
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection::<Task>(constants::MONGO_TASK_COLLECTION);
-    
-    collection.count_documents(doc! {}, None).await
+// Count tasks per value of `field` into `StatCount`s, largest count first.
+fn count_stages(field: &str) -> Vec<Document> {
+    vec![
+        doc! { "$group": { "_id": field, "count": { "$sum": 1 } } },
+        doc! { "$project": { "_id": 0, "key": "$_id", "count": 1 } },
+        doc! { "$sort": { "count": -1, "key": 1 } },
+    ]
+}
+
+// Filter for a write on one task, optionally checked against the accepted versions.
+// Tasks in the trash can not be written.
+fn write_filter(id: &str, versions: Option<&[i64]>) -> Document {
+    let mut filter = doc! { "_id": id, "deleted_at": null };
+    if let Some(versions) = versions {
+        filter.insert("version", version_filter(versions));
+    }
+    filter
+}
+
+fn trash_update_doc() -> Document {
+    doc! {
+        "$set": { "deleted_at": DateTime::now() },
+        "$inc": { "version": 1_i64 },
+    }
+}
+
+fn patch_update_doc(patch: &TaskPatch) -> Result<Document, Error> {
+    let mut update_doc = to_update_doc(bson::to_document(patch)?);
+    update_doc.insert("$inc", doc! { "version": 1_i64 });
+    Ok(update_doc)
 }
 
+// A new task as stored, with a new unique id, the first version and the creation time.
+// Imported tasks keep the creation time of their export.
+pub fn new_task_doc(task: Task) -> Task {
+    let created_at = DateTime::now()
+        .to_chrono()
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+    Task {
+        id: Some(nanoid!()),
+        version: 1,
+        created_at: task.created_at.clone().or(Some(created_at)),
+        ..task
+    }
+}
+
+// Writes of a bulk request, each with its request index.
+pub type IndexedWrites<T> = Vec<(usize, T)>;
+
+// Give creates their ids and keep the request index of every write.
+pub fn split_creates(writes: Vec<TaskWrite>) -> (IndexedWrites<Task>, IndexedWrites<TaskWrite>) {
+    let mut creates = Vec::new();
+    let mut others = Vec::new();
+    for (index, write) in writes.into_iter().enumerate() {
+        match write {
+            TaskWrite::Create(task) => creates.push((index, new_task_doc(task))),
+            write => others.push((index, write)),
+        }
+    }
+    (creates, others)
+}
+
+// Run an update or a delete, returns whether a task matched.
+async fn write_one(
+    collection: &Collection<Task>,
+    write: TaskWrite,
+    session: Option<&mut ClientSession>,
+) -> Result<bool, Error> {
+    match write {
+        TaskWrite::Update { id, patch, versions } => {
+            let filter = write_filter(&id, versions.as_deref());
+            let update_doc = patch_update_doc(&patch)?;
+            let result = match session {
+                Some(session) => {
+                    collection
+                        .update_one_with_session(filter, update_doc, None, session)
+                        .await?
+                }
+                None => collection.update_one(filter, update_doc, None).await?,
+            };
+            Ok(result.matched_count == 1)
+        }
+        TaskWrite::Delete { id, versions } => {
+            let filter = write_filter(&id, versions.as_deref());
+            let update_doc = trash_update_doc();
+            let result = match session {
+                Some(session) => {
+                    collection
+                        .update_one_with_session(filter, update_doc, None, session)
+                        .await?
+                }
+                None => collection.update_one(filter, update_doc, None).await?,
+            };
+            Ok(result.matched_count == 1)
+        }
+        // Creates are split off and inserted together.
+        TaskWrite::Create(_) => Ok(false),
+    }
+}
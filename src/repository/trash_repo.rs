@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    error::Error,
+    Collection,
+};
+
+use crate::config::state::AppState;
+use crate::metrics;
+use crate::models::trash_model::TrashItem;
+use crate::telemetry;
+
+// Soft deleted tasks and users, across both collections.
+#[async_trait]
+pub trait TrashRepository: Send + Sync {
+    // Soft deleted tasks and users together, most recently deleted first.
+    async fn get_trash(&self, offset: u64, limit: i64) -> Result<Vec<TrashItem>, Error>;
+
+    async fn get_trash_size(&self) -> Result<u64, Error>;
+
+    // Permanently remove tasks and users deleted before the cutoff.
+    // Returns the number of removed documents.
+    async fn purge_trash(&self, cutoff: DateTime) -> Result<u64, Error>;
+}
+
+// Trash repository on the MongoDB task and user collections.
+pub struct MongoTrashRepository {
+    tasks: Collection<Document>,
+    users: Collection<Document>,
+}
+
+impl MongoTrashRepository {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            tasks: state.task_documents(),
+            users: state.user_documents(),
+        }
+    }
+}
+
+// Projection of a soft deleted document into a `TrashItem`.
+fn trash_projection(kind: &str, name_field: &str) -> Document {
+    doc! {
+        "$project": {
+            "_id": 0,
+            "id": "$_id",
+            "kind": kind,
+            "name": format!("${}", name_field),
+            "deleted_at": { "$dateToString": { "date": "$deleted_at" } },
+            "deleted_on": "$deleted_at",
+        }
+    }
+}
+
+#[async_trait]
+impl TrashRepository for MongoTrashRepository {
+    async fn get_trash(&self, offset: u64, limit: i64) -> Result<Vec<TrashItem>, Error> {
+        let _timer = metrics::mongo_timer("trash_repo", "get_trash");
+        let _span = telemetry::span("trash_repo", "get_trash");
+        let pipeline = vec![
+            doc! { "$match": { "deleted_at": { "$ne": null } } },
+            trash_projection("task", "title"),
+            doc! {
+                "$unionWith": {
+                    "coll": self.users.name(),
+                    "pipeline": [
+                        { "$match": { "deleted_at": { "$ne": null } } },
+                        trash_projection("user", "name"),
+                    ],
+                }
+            },
+            doc! { "$sort": { "deleted_on": -1, "id": 1 } },
+            doc! { "$skip": offset as i64 },
+            doc! { "$limit": limit },
+            doc! { "$unset": "deleted_on" },
+        ];
+
+        let mut cursors = self.tasks.aggregate(pipeline, None).await?;
+        let mut items: Vec<TrashItem> = Vec::new();
+        while let Some(item) = cursors.try_next().await? {
+            items.push(bson::from_document(item)?);
+        }
+        Ok(items)
+    }
+
+    async fn get_trash_size(&self) -> Result<u64, Error> {
+        let _timer = metrics::mongo_timer("trash_repo", "get_trash_size");
+        let _span = telemetry::span("trash_repo", "get_trash_size");
+        let filter = doc! { "deleted_at": { "$ne": null } };
+        let tasks = self
+            .tasks
+            .count_documents(filter.clone(), None)
+            .await?;
+        let users = self
+            .users
+            .count_documents(filter, None)
+            .await?;
+        Ok(tasks + users)
+    }
+
+    async fn purge_trash(&self, cutoff: DateTime) -> Result<u64, Error> {
+        let _timer = metrics::mongo_timer("trash_repo", "purge_trash");
+        let _span = telemetry::span("trash_repo", "purge_trash");
+        let filter = doc! { "deleted_at": { "$lt": cutoff } };
+        let tasks = self
+            .tasks
+            .delete_many(filter.clone(), None)
+            .await?;
+        let users = self
+            .users
+            .delete_many(filter, None)
+            .await?;
+        Ok(tasks.deleted_count + users.deleted_count)
+    }
+}
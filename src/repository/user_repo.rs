@@ -1,94 +1,267 @@
-use actix_web::web::Data;
-use futures::TryStreamExt;
-use mongodb::options::FindOptions;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::{Stream, TryStreamExt};
+use mongodb::options::{FindOptions, InsertManyOptions};
 use mongodb::{
-    bson::doc,
-    error::Error,
-    results::{DeleteResult, UpdateResult},
-    Client, Collection,
+    bson::{doc, DateTime},
+    error::{Error, ErrorKind},
+    Collection,
 };
 use nanoid::nanoid;
 
+use crate::config::state::AppState;
+use crate::metrics;
+use crate::models::cursor_model::Cursor;
 use crate::models::user_list_response::Users;
-use crate::{constants, models::user_model::User};
-
-// Add a new user to Mongo DB.
-pub async fn create_user(client: &Data<Client>, new_user: User) -> Result<Option<User>, Error> {
-    let new_doc = User {
-        id: Some(nanoid!()),
-        name: new_user.name,
-        location: new_user.location,
-        title: new_user.title,
-    };
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    let added_user = collection.insert_one(new_doc, None).await;
-    // On successful add.. Retrieve the added record as response.
-    match added_user {
-        Ok(u) => collection.find_one(doc! {"_id": u.inserted_id}, None).await,
-        Err(err) => Err(err),
-    }
+use crate::models::etag_model::version_filter;
+use crate::models::patch_model::to_update_doc;
+use crate::models::user_model::{User, UserPatch};
+use crate::telemetry;
+
+// Every user of an export, read one by one.
+pub type UserStream = Pin<Box<dyn Stream<Item = Result<User, Error>> + Send>>;
+
+// User storage used by the user service.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    // Add a new user with a new unique id and return it as stored.
+    async fn create_user(&self, new_user: User) -> Result<Option<User>, Error>;
+
+    // Add many users at once, without stopping at the first failure.
+    // Returns the new id of every user, or the error that rejected it.
+    async fn create_users(&self, new_users: Vec<User>) -> Vec<Result<String, Error>>;
+
+    // Users in the trash are never returned.
+    async fn get_user(&self, id: &str) -> Result<Option<User>, Error>;
+
+    // Update a user for give unique user id, returns whether it matched.
+    // When `versions` is given the user must currently be at one of them.
+    async fn update_user(
+        &self,
+        id: &str,
+        patch: &UserPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error>;
+
+    // Move a user to the trash, the purge job removes it later.
+    async fn delete_user(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error>;
+
+    async fn restore_user(&self, id: &str) -> Result<bool, Error>;
+
+    // Users sorted by (name, id).
+    async fn get_all_users(&self, offset: u64, limit: i64) -> Result<Vec<Users>, Error>;
+
+    // Users with keyset pagination on (name, id).
+    // Ascending after the cursor, descending before it.
+    async fn get_users_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Users>, Error>;
+
+    async fn get_users_size(&self) -> Result<u64, Error>;
+
+    // Every user, in the order of the list endpoint.
+    async fn export_users(&self) -> Result<UserStream, Error>;
 }
 
-// Get a user by given id from MongoDB database
-pub async fn get_user(client: &Data<Client>, id: &String) -> Result<Option<User>, Error> {
-    let obj_id = String::from(id);
-    let filter = doc! {"_id": obj_id};
-    let collection = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    collection.find_one(filter, None).await
+// User repository on the MongoDB user collection.
+pub struct MongoUserRepository {
+    collection: Collection<User>,
 }
 
-// Update a user for give unique user id.
-pub async fn update_user(
-    client: &Data<Client>,
-    id: &String,
-    new_user: User,
-) -> Result<UpdateResult, Error> {
-    let obj_id = String::from(id);
-    let filter = doc! {"_id": obj_id};
-    let new_doc = doc! {
-        "$set":
-            {
-                "_id": new_user.id,
-                "name": new_user.name,
-                "location": new_user.location,
-                "title": new_user.title
-            },
-    };
-    let collection: Collection<User> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    collection.update_one(filter, new_doc, None).await
+impl MongoUserRepository {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            collection: state.users.clone(),
+        }
+    }
 }
 
-// Delete a user for given unique user id.
-pub async fn delete_user(client: &Data<Client>, id: &String) -> Result<DeleteResult, Error> {
-    let obj_id = String::from(id);
-    let filter = doc! {"_id": obj_id};
-    let collection: Collection<User> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    collection.delete_one(filter, None).await
+#[async_trait]
+impl UserRepository for MongoUserRepository {
+    async fn create_user(&self, new_user: User) -> Result<Option<User>, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "create_user");
+        let _span = telemetry::span("user_repo", "create_user");
+        let new_doc = User {
+            id: Some(nanoid!()),
+            name: new_user.name,
+            location: new_user.location,
+            title: new_user.title,
+            version: 1,
+        };
+        let collection = &self.collection;
+        let added_user = collection.insert_one(new_doc, None).await;
+        // On successful add.. Retrieve the added record as response.
+        match added_user {
+            Ok(u) => collection.find_one(doc! {"_id": u.inserted_id}, None).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn create_users(&self, new_users: Vec<User>) -> Vec<Result<String, Error>> {
+        let _timer = metrics::mongo_timer("user_repo", "create_users");
+        let _span = telemetry::span("user_repo", "create_users");
+        let new_docs: Vec<User> = new_users
+            .into_iter()
+            .map(|user| User {
+                id: Some(nanoid!()),
+                name: user.name,
+                location: user.location,
+                title: user.title,
+                version: 1,
+            })
+            .collect();
+        if new_docs.is_empty() {
+            return Vec::new();
+        }
+        let options = InsertManyOptions::builder().ordered(false).build();
+        let result = self.collection.insert_many(&new_docs, options).await;
+        new_docs
+            .into_iter()
+            .enumerate()
+            .map(|(position, user)| match &result {
+                Ok(_) => Ok(user.id.unwrap_or_default()),
+                Err(err) => match err.kind.as_ref() {
+                    // Only the rejected documents failed, unless the whole call did.
+                    ErrorKind::BulkWrite(failure) => match &failure.write_errors {
+                        Some(errors) if !errors.iter().any(|e| e.index == position) => {
+                            Ok(user.id.unwrap_or_default())
+                        }
+                        _ => Err(err.clone()),
+                    },
+                    _ => Err(err.clone()),
+                },
+            })
+            .collect()
+    }
+
+    async fn get_user(&self, id: &str) -> Result<Option<User>, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "get_user");
+        let _span = telemetry::span("user_repo", "get_user");
+        let obj_id = String::from(id);
+        let filter = doc! {"_id": obj_id, "deleted_at": null};
+        self.collection.find_one(filter, None).await
+    }
+
+    // Only the fields present in the patch are touched with `$set`/`$unset`.
+    async fn update_user(
+        &self,
+        id: &str,
+        patch: &UserPatch,
+        versions: Option<&[i64]>,
+    ) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "update_user");
+        let _span = telemetry::span("user_repo", "update_user");
+        let obj_id = String::from(id);
+        let mut filter = doc! {"_id": obj_id, "deleted_at": null};
+        if let Some(versions) = versions {
+            filter.insert("version", version_filter(versions));
+        }
+        let mut new_doc = to_update_doc(bson::to_document(patch)?);
+        new_doc.insert("$inc", doc! {"version": 1_i64});
+        let result = self.collection.update_one(filter, new_doc, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    // The user is moved to the trash by setting `deleted_at`.
+    async fn delete_user(&self, id: &str, versions: Option<&[i64]>) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "delete_user");
+        let _span = telemetry::span("user_repo", "delete_user");
+        let obj_id = String::from(id);
+        let mut filter = doc! {"_id": obj_id, "deleted_at": null};
+        if let Some(versions) = versions {
+            filter.insert("version", version_filter(versions));
+        }
+        let new_doc = doc! {
+            "$set": {"deleted_at": DateTime::now()},
+            "$inc": {"version": 1_i64},
+        };
+        let result = self.collection.update_one(filter, new_doc, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn restore_user(&self, id: &str) -> Result<bool, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "restore_user");
+        let _span = telemetry::span("user_repo", "restore_user");
+        let obj_id = String::from(id);
+        let filter = doc! {"_id": obj_id, "deleted_at": {"$ne": null}};
+        let new_doc = doc! {
+            "$unset": {"deleted_at": ""},
+            "$inc": {"version": 1_i64},
+        };
+        let result = self.collection.update_one(filter, new_doc, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn get_all_users(&self, offset: u64, limit: i64) -> Result<Vec<Users>, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "get_all_users");
+        let _span = telemetry::span("user_repo", "get_all_users");
+        let find_options = FindOptions::builder()
+            .skip(offset)
+            .limit(limit)
+            .sort(doc! {"name": 1, "_id": 1})
+            .build();
+        let cursors = self
+            .collection
+            .find(doc! {"deleted_at": null}, find_options)
+            .await?;
+        collect_users(cursors).await
+    }
+
+    async fn get_users_by_cursor(
+        &self,
+        cursor: Option<&Cursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<Users>, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "get_users_by_cursor");
+        let _span = telemetry::span("user_repo", "get_users_by_cursor");
+        let (op, direction) = if backward { ("$lt", -1) } else { ("$gt", 1) };
+        let mut filter = doc! {"deleted_at": null};
+        if let Some(c) = cursor {
+            filter.insert(
+                "$or",
+                vec![
+                    doc! {"name": {op: &c.key}},
+                    doc! {"name": &c.key, "_id": {op: &c.id}},
+                ],
+            );
+        }
+        let find_options = FindOptions::builder()
+            .limit(limit)
+            .sort(doc! {"name": direction, "_id": direction})
+            .build();
+        let cursors = self.collection.find(filter, find_options).await?;
+        collect_users(cursors).await
+    }
+
+    async fn get_users_size(&self) -> Result<u64, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "get_users_size");
+        let _span = telemetry::span("user_repo", "get_users_size");
+        self.collection
+            .count_documents(doc! {"deleted_at": null}, None)
+            .await
+    }
+
+    // Streams straight from the Mongo cursor.
+    async fn export_users(&self) -> Result<UserStream, Error> {
+        let _timer = metrics::mongo_timer("user_repo", "export_users");
+        let _span = telemetry::span("user_repo", "export_users");
+        let find_options = FindOptions::builder()
+            .sort(doc! {"name": 1, "_id": 1})
+            .build();
+        let cursor = self
+            .collection
+            .find(doc! {"deleted_at": null}, find_options)
+            .await?;
+        Ok(Box::pin(cursor))
+    }
 }
 
-// Fetch all users from the database
-pub async fn get_all_users(
-    client: &Data<Client>,
-    offset: u64,
-    limit: i64,
-) -> Result<Vec<Users>, Error> {
-    let collection: Collection<User> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    let find_options = FindOptions::builder()
-        .skip(offset)
-        .limit(limit)
-        .sort(doc! {"name": 1})
-        .build();
-    let mut cursors = collection.find(None, find_options).await?;
+async fn collect_users(mut cursors: mongodb::Cursor<User>) -> Result<Vec<Users>, Error> {
     let mut users: Vec<Users> = Vec::new();
     while let Some(user) = cursors.try_next().await? {
         users.push(Users {
@@ -100,10 +273,3 @@ pub async fn get_all_users(
     }
     Ok(users)
 }
-
-pub async fn get_users_size(client: &Data<Client>) -> Result<u64, Error> {
-    let collection: Collection<User> = client
-        .database(constants::MONGO_DATABASE)
-        .collection(constants::MONGO_USER_COLLECTION);
-    collection.count_documents(doc! {}, None).await
-}
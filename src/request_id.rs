@@ -0,0 +1,116 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage};
+use nanoid::nanoid;
+
+use crate::constants;
+
+// Correlation id of a request, in its extensions and in the `request_id` log MDC.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+// Id of the request being served on this thread, if any.
+pub fn current() -> Option<String> {
+    log_mdc::get("request_id", |id| id.map(str::to_owned))
+}
+
+// A client id is kept when it is printable ASCII of a sensible length.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= constants::MAX_REQUEST_ID_LENGTH
+        && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Middleware taking the `X-Request-Id` of the client, or generating one,
+// and echoing it on the response. Every log record of the request carries it.
+pub struct RequestIds;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIds
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdsMiddleware { service }))
+    }
+}
+
+pub struct RequestIdsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = req
+            .headers()
+            .get(constants::REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid(id))
+            .map(str::to_owned)
+            .unwrap_or_else(|| nanoid!());
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        let future = {
+            let _logged = log_mdc::insert_scoped("request_id", id.as_str());
+            self.service.call(req)
+        };
+        let header = id.clone();
+        let future = async move {
+            let name = HeaderName::from_static(constants::REQUEST_ID_HEADER);
+            // Only valid header characters get here.
+            let value = HeaderValue::from_str(&header).unwrap();
+            match future.await {
+                Ok(mut response) => {
+                    response.headers_mut().insert(name, value);
+                    Ok(response)
+                }
+                // Errors of inner middlewares become responses here to carry the header.
+                Err(err) => {
+                    let mut response = err.error_response();
+                    response.headers_mut().insert(name, value);
+                    Err(InternalError::from_response(err.to_string(), response).into())
+                }
+            }
+        };
+        Box::pin(WithRequestId { future: Box::pin(future), id })
+    }
+}
+
+// A future polled with the request id in the log MDC.
+struct WithRequestId<F> {
+    future: Pin<Box<F>>,
+    id: String,
+}
+
+impl<F: Future> Future for WithRequestId<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, task: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        let _logged = log_mdc::insert_scoped("request_id", this.id.as_str());
+        this.future.as_mut().poll(task)
+    }
+}
@@ -1,8 +1,11 @@
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use crate::metrics;
+use crate::telemetry;
 
-#[derive(Deserialize)]
+
+#[derive(Serialize, Deserialize)]
 pub struct AggregatedData {
     pub data1 : String,
     pub data2 : String,
@@ -10,18 +13,28 @@ pub struct AggregatedData {
 
 pub struct AggregatorService {
     client: Client,
+    base_url: String,
 }
 
 impl AggregatorService {
-    pub fn new() -> Self {
+    pub fn new(base_url: &str) -> Self {
         Self  {
             client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
         }
     }
 
     pub async fn fetch_data(&self) -> Result<AggregatedData, reqwest::Error> {
-        let response1 = self.client.get("http://api.example.com/data1").send().await?;
-        let response2 = self.client.get("http://api.example.com/data2").send().await?;
+        let data1_call = self.client
+            .get(format!("{}/data1", self.base_url))
+            .headers(telemetry::trace_headers())
+            .send();
+        let response1 = metrics::outbound("aggregator_service", data1_call).await?;
+        let data2_call = self.client
+            .get(format!("{}/data2", self.base_url))
+            .headers(telemetry::trace_headers())
+            .send();
+        let response2 = metrics::outbound("aggregator_service", data2_call).await?;
 
         let data1: String = response1.text().await?;
         let data2: String = response2.text().await?;
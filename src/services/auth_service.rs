@@ -1,22 +1,22 @@
-use actix_web::web::Data;
 use actix_web::HttpResponse;
 use argon2::{Config, Variant, Version};
 use chrono::Utc;
-use log::{error, warn};
-use mongodb::Client;
+use log::warn;
 use nanoid::nanoid;
 use rand::{thread_rng, Rng};
 
 use crate::auth::claims::Claims;
+use crate::config::settings::JwtSettings;
+use crate::metrics;
 use crate::{
     api::auth_api::{LoginRequest, RegisterRequest, RegisterResponse},
     models::auth_model::Auth,
     models::error_model::ApiErrorType,
-    repository::auth_repo,
+    repository::auth_repo::AuthRepository,
 };
 
 pub async fn create_user(
-    client: &Data<Client>,
+    repo: &dyn AuthRepository,
     register_user: RegisterRequest,
 ) -> Result<HttpResponse, ApiErrorType> {
     // Step 1: Hash password with argon2.
@@ -33,10 +33,16 @@ pub async fn create_user(
         ad: &[],
         hash_length: 64,
     };
+    let timer = metrics::password_timer("hash");
     let hash = argon2::hash_encoded(register_user.password.as_bytes(), &salt, &config);
+    timer.observe_duration();
 
     // Step 2: Verify user email does not already exists.
-    if auth_repo::check_email(client, &register_user.email).await {
+    let email_free = match repo.check_email(&register_user.email).await {
+        Ok(free) => free,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
+    if email_free {
         // Step 3: Store user to MongoDB.
         let current_time = Utc::now();
         let user = Auth {
@@ -49,53 +55,63 @@ pub async fn create_user(
             reset_password: false,
             password_hash: match hash {
                 Ok(pwd_hash) => pwd_hash,
-                Err(_) => return Err(ApiErrorType::InternalServerError),
+                Err(err) => return Err(ApiErrorType::internal(err)),
             },
             created_ts: current_time,
             updated_ts: current_time,
         };
 
-        let registered_user = auth_repo::auth_register(client, user).await;
+        let registered_user = repo.auth_register(user).await;
         match registered_user {
             // User Registered successfully.
-            Ok(_) => Ok(HttpResponse::Created().json(RegisterResponse {
+            Ok(true) => Ok(HttpResponse::Created().json(RegisterResponse {
                 status: "Success".to_owned(),
                 message: "User registered successfully".to_owned(),
             })),
+            // Registered by a concurrent request since the email check.
+            Ok(false) => Ok(user_exists()),
             // Internal Server Error.
-            Err(err) => {
-                error!("Error: {}", err);
-                Err(ApiErrorType::InternalServerError)
-            }
+            Err(err) => Err(ApiErrorType::internal(err)),
         }
     } else {
         // User with email already exists.
-        Ok(HttpResponse::BadRequest().json(RegisterResponse {
-            status: "Failed".to_owned(),
-            message: "User already exists with email".to_owned(),
-        }))
+        Ok(user_exists())
     }
 }
 
+fn user_exists() -> HttpResponse {
+    HttpResponse::BadRequest().json(RegisterResponse {
+        status: "Failed".to_owned(),
+        message: "User already exists with email".to_owned(),
+    })
+}
+
 // Login with credentials and generate JWT token after successful login.
 pub async fn login(
-    client: &Data<Client>,
+    repo: &dyn AuthRepository,
+    jwt: &JwtSettings,
     login_request: LoginRequest,
 ) -> Result<HttpResponse, ApiErrorType> {
     // Step 1: Get auth user from MongoDB by email id.
-    let auth_user = auth_repo::fetch_by_email(client, &login_request.email).await;
+    let auth_user = match repo.fetch_by_email(&login_request.email).await {
+        Ok(auth) => auth,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
 
     // Check if the user
     match &auth_user {
         Some(a) => {
             // Step 2: Check password with hashed password from Database.
+            let timer = metrics::password_timer("verify");
             let pwd_match =
                 argon2::verify_encoded(&a.password_hash, login_request.password.as_bytes())
                     .unwrap();
+            timer.observe_duration();
+            metrics::login(pwd_match);
             if pwd_match {
                 // Credentials verified successfully.
                 // Step 3: Generate JWT token with auth information.
-                match Claims::create_jwt_token(a) {
+                match Claims::create_jwt_token(a, jwt) {
                     Ok(response) => Ok(HttpResponse::Ok().json(response)),
                     Err(_) => Err(ApiErrorType::AuthenticationError),
                 }
@@ -104,6 +120,7 @@ pub async fn login(
             }
         }
         None => {
+            metrics::login(false);
             warn!("User not found for email {}", login_request.email);
             Err(ApiErrorType::InvalidCredential)
         }
@@ -0,0 +1,111 @@
+use std::future::Future;
+
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use log::{error, warn};
+use mongodb::error::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use validator::Validate;
+
+use crate::config::settings::{BulkSettings, ErrorSettings};
+use crate::models::bulk_model::BulkItemResult;
+use crate::models::error_model::ApiErrorType;
+use crate::models::export_model::{parse_error, ExportFormat, ImportResponse};
+
+// Stream records as CSV or NDJSON, downloaded as `<name>.<extension>`.
+// `columns` is the CSV header, in the field order of the record.
+pub fn export<R, S>(rows: S, format: ExportFormat, name: &str, columns: &[&str]) -> HttpResponse
+where
+    R: Serialize,
+    S: Stream<Item = Result<R, Error>> + 'static,
+{
+    let header = stream::once(future::ready(Ok(format.header(columns))));
+    let name = name.to_owned();
+    let log_name = name.clone();
+    let rows = rows.map(move |row| match row {
+        Ok(row) => Ok(format.encode(&row)),
+        Err(err) => {
+            error!("Error exporting {} : {}", log_name, err);
+            Err(err)
+        }
+    });
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("{}.{}", name, format.extension()))],
+        })
+        .streaming(header.chain(rows))
+}
+
+// Import records from a CSV or NDJSON upload.
+// Every row is parsed with `parse` and validated. Valid rows are written together with
+// `write`, which returns the new id or the error of each one, even when other rows fail.
+pub async fn import<R, T, P, W, F>(
+    body: &[u8],
+    format: ExportFormat,
+    object: &str,
+    limits: &BulkSettings,
+    errors: &ErrorSettings,
+    parse: P,
+    write: W,
+) -> Result<HttpResponse, ApiErrorType>
+where
+    R: DeserializeOwned,
+    T: Validate,
+    P: Fn(R) -> Result<T, String>,
+    W: FnOnce(Vec<T>) -> F,
+    F: Future<Output = Vec<Result<String, ApiErrorType>>>,
+{
+    let rows = format.decode::<R>(body);
+    if rows.len() > limits.max_operations {
+        warn!("{} import with {} rows rejected", object, rows.len());
+        return Err(ApiErrorType::BadRequest);
+    }
+
+    let mut results: Vec<Option<BulkItemResult>> = Vec::new();
+    let mut records: Vec<T> = Vec::new();
+    let mut pending: Vec<usize> = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let record = match row.and_then(&parse) {
+            Ok(record) => record,
+            Err(message) => {
+                warn!("Parse Error on imported {} {}: {}", object, index, message);
+                results.push(Some(parse_error(index, message)));
+                continue;
+            }
+        };
+        if let Err(err) = record.validate() {
+            warn!("Payload validation Error on imported {} {}: {}", object, index, err);
+            let error = ApiErrorType::ValidationError {
+                validation_error: err,
+                object: object.to_string(),
+            };
+            results.push(Some(BulkItemResult::failure(index, None, &error)));
+            continue;
+        }
+        results.push(None);
+        pending.push(index);
+        records.push(record);
+    }
+
+    let outcomes = write(records).await;
+    for (index, outcome) in pending.into_iter().zip(outcomes) {
+        let result = match outcome {
+            Ok(new_id) => BulkItemResult::success(index, Some(new_id), StatusCode::CREATED),
+            Err(err) => BulkItemResult::failure(index, None, &err),
+        };
+        results[index] = Some(result);
+    }
+
+    let mut results: Vec<BulkItemResult> = results.into_iter().flatten().collect();
+    if !errors.expose_details {
+        results.iter_mut().for_each(BulkItemResult::redact);
+    }
+    let response = ImportResponse::new(results);
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::HttpResponse;
+use async_trait::async_trait;
+use log::warn;
+use mongodb::{bson::doc, Database};
+use nanoid::nanoid;
+
+use crate::config::settings::{ErrorSettings, HealthSettings};
+use crate::models::health_model::{CheckReport, HealthReport, HealthStatus};
+
+// A dependency the app needs to serve traffic, checked by the readiness endpoint.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Whether the app is unready while this dependency is unhealthy.
+    fn required(&self) -> bool {
+        true
+    }
+
+    // Err with the reason when the dependency is unhealthy.
+    async fn check(&self) -> Result<(), String>;
+}
+
+// Checks registered at startup. Each one is bounded by `health.check_timeout_ms`.
+#[derive(Clone, Default)]
+pub struct HealthChecks {
+    checks: Vec<Arc<dyn HealthCheck>>,
+}
+
+impl HealthChecks {
+    pub fn register(&mut self, check: impl HealthCheck + 'static) {
+        self.checks.push(Arc::new(check));
+    }
+}
+
+// Runs the `ping` command on the application database.
+pub struct MongoHealthCheck {
+    database: Database,
+}
+
+impl MongoHealthCheck {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for MongoHealthCheck {
+    fn name(&self) -> &str {
+        "mongodb"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        self.database
+            .run_command(doc! {"ping": 1}, None)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+// The process is up and serving requests.
+pub fn live() -> HttpResponse {
+    HttpResponse::Ok().json(HealthReport {
+        status: HealthStatus::Up,
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        checks: Vec::new(),
+    })
+}
+
+// Run every check, 503 when a required one is unhealthy.
+// Failures are logged with an error id, their reason is only sent when details are exposed.
+pub async fn ready(
+    health: &HealthChecks,
+    settings: &HealthSettings,
+    errors: &ErrorSettings,
+) -> HttpResponse {
+    let timeout = Duration::from_millis(settings.check_timeout_ms);
+    let mut checks = Vec::new();
+    for check in &health.checks {
+        let started = Instant::now();
+        let result = match actix_web::rt::time::timeout(timeout, check.check()).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("no answer within {:?}", timeout)),
+        };
+        let latency_ms = started.elapsed().as_millis();
+        let error_id = result.as_ref().err().map(|err| {
+            let error_id = nanoid!();
+            warn!("Health check {} failed {} : {}", check.name(), error_id, err);
+            error_id
+        });
+        checks.push(CheckReport {
+            name: check.name().to_owned(),
+            status: if result.is_ok() { HealthStatus::Up } else { HealthStatus::Down },
+            required: check.required(),
+            latency_ms,
+            error_id,
+            error: result.err().filter(|_| errors.expose_details),
+        });
+    }
+
+    let ready = checks
+        .iter()
+        .all(|check| check.status == HealthStatus::Up || !check.required);
+    let report = HealthReport {
+        status: if ready { HealthStatus::Up } else { HealthStatus::Down },
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        checks,
+    };
+    if ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
@@ -1,21 +1,23 @@
 use actix_web::HttpResponse;
-use log::error;
 
+use crate::metrics;
 use crate::models::error_model::ApiErrorType;
 use crate::models::location_model::Location;
+use crate::telemetry;
 
 pub async fn get_location() -> Result<HttpResponse, ApiErrorType> {
     match ip_loc().await {
         Ok(r) => Ok(HttpResponse::Ok().json(r)),
-        Err(e) => {
-            error!("Error calling REST API: {}", e);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Err(e) => Err(ApiErrorType::internal(e)),
     }
 }
 
 async fn ip_loc() -> Result<Location, reqwest::Error> {
-    let response = reqwest::get("https://ifconfig.co/json")
+    let call = reqwest::Client::new()
+        .get("https://ifconfig.co/json")
+        .headers(telemetry::trace_headers())
+        .send();
+    let response = metrics::outbound("location_service", call)
         .await?
         .json::<Location>()
         .await?;
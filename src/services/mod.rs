@@ -1,5 +1,8 @@
 pub mod auth_service;
+pub mod health_service;
 pub mod location_service;
 pub mod user_service;
 pub mod task_service;
-pub mod aggregator_service;
\ No newline at end of file
+pub mod aggregator_service;
+pub mod export_service;
+pub mod trash_service;
\ No newline at end of file
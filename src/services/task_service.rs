@@ -1,222 +1,472 @@
-use actix_web::web::{Data, Json, Path};
-use actix_web::HttpResponse;
-use log::{error, warn};
-use mongodb::bson::{doc, Bson};
+use std::collections::HashMap;
+
+use actix_web::web::{Json, Path};
+use actix_web::http::header::{ETag, IfMatch, IfNoneMatch};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{Days, NaiveDate};
+use log::warn;
+use mongodb::bson::DateTime;
 use mongodb::error::Error;
-use mongodb::Client;
-use mongodb::Collection;
 use futures::stream::StreamExt;
+use validator::Validate;
 
-use crate::api::task_api::Pagination;
-use crate::constants;
-use crate::models::task_list_response::{Link, LinkHref, Meta, TaskListResponse};
+use crate::api::task_api::{Pagination, StatsRange};
+use crate::config::settings::{BulkSettings, ErrorSettings, PaginationSettings, StatsSettings};
+use crate::models::bulk_model::{BulkItemResult, BulkResponse, BulkTaskOperation, BulkTaskRequest};
+use crate::models::cursor_model::Cursor;
+use crate::models::etag_model::{etag, if_match_versions, is_not_modified};
+use crate::models::export_model::ExportFormat;
+use crate::models::page_response::{page_limit, Keyset, PageBuilder};
+use crate::models::stats_model::{StatCount, TaskStatsResponse};
 use crate::models::error_model::ApiErrorType;
-use crate::models::task_model::{Task, TaskAggregate};
-use crate::repository::task_repo;
+use crate::models::task_model::{Task, TaskPatch, TaskRecord};
+use crate::repository::task_repo::{TaskRepository, TaskWrite, WriteOutcome};
+use crate::services::export_service;
 
 
 // Add a new task to MongoDB
 pub async fn create_task(
-    client: &Data<Client>,
+    repo: &dyn TaskRepository,
     new_task: Json<Task>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let data = Task {
         id: None,
-        title: new_task.title.to_owned(),
-        body: new_task.body.to_owned(),
+        created_at: None,
+        version: 0,
+        ..new_task.into_inner()
     };
-    let task_detail = task_repo::create_task(client, data).await;
+    let task_detail = repo.create_task(data).await;
     match task_detail {
-        Ok(Some(task)) => Ok(HttpResponse::Created().json(task)),
-        Ok(None) => Err(ApiErrorType::InternalServerError),
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Ok(Some(task)) => Ok(HttpResponse::Created()
+            .insert_header(ETag(etag(task.version)))
+            .json(task)),
+        Ok(None) => Err(ApiErrorType::internal("created document not found")),
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 // Get a task by given id from MongoDB database
 pub async fn get_task_by_id(
-    client: &Data<Client>,
+    repo: &dyn TaskRepository,
     path: Path<String>,
+    if_none_match: Option<IfNoneMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         warn!("Task with id - {} not found for get task by ID", id);
         return Err(ApiErrorType::BadRequest);
     }
-    let task_detail = task_repo::get_task(client, &id).await;
-    handle_optional_task_response(task_detail)
+    let task_detail = repo.get_task(&id).await;
+    match task_detail {
+        Ok(Some(task)) if is_not_modified(if_none_match, task.version) => {
+            Ok(HttpResponse::NotModified()
+                .insert_header(ETag(etag(task.version)))
+                .finish())
+        }
+        _ => handle_optional_task_response(task_detail),
+    }
 }
 
 // Update a task for a given unique task id.
 pub async fn update_task(
-    client: &Data<Client>,
+    repo: &dyn TaskRepository,
     path: Path<String>,
     update_task: Json<Task>,
+    if_match: Option<IfMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         return Err(ApiErrorType::BadRequest);
     };
-    let data = Task {
-        id: Some(String::from(&id)),
-        title: update_task.title.to_owned(),
-        body: update_task.body.to_owned(),
+    let data = TaskPatch::replace(update_task.into_inner());
+    let versions = if_match_versions(if_match);
+    apply_task_patch(repo, &id, data, versions.as_deref()).await
+}
+
+// Partially update a task with a JSON merge patch.
+pub async fn patch_task(
+    repo: &dyn TaskRepository,
+    path: Path<String>,
+    patch: TaskPatch,
+    if_match: Option<IfMatch>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let id = path.into_inner();
+    if id.is_empty() {
+        return Err(ApiErrorType::BadRequest);
     };
+    let versions = if_match_versions(if_match);
+    // Nothing to change, answer with the current task.
+    if patch.is_empty() {
+        let task_detail = repo.get_task(&id).await;
+        if let (Ok(Some(task)), Some(versions)) = (&task_detail, &versions) {
+            if !versions.contains(&task.version) {
+                return Err(ApiErrorType::PreconditionFailed);
+            }
+        }
+        return handle_optional_task_response(task_detail);
+    }
+    apply_task_patch(repo, &id, patch, versions.as_deref()).await
+}
 
-    let update_result = task_repo::update_task(client, &id, data).await;
+async fn apply_task_patch(
+    repo: &dyn TaskRepository,
+    id: &String,
+    patch: TaskPatch,
+    versions: Option<&[i64]>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let update_result = repo.update_task(id, &patch, versions).await;
     match update_result {
-        Ok(update) => {
-            if update.matched_count == 1 {
-                let updated_task_info = task_repo::get_task(client, &id).await;
+        Ok(matched) => {
+            if matched {
+                let updated_task_info = repo.get_task(id).await;
                 handle_optional_task_response(updated_task_info)
             } else {
-                warn!("Task with id -{} not found to update task by ID", id);
-                Err(ApiErrorType::TaskNotFound)
+                Err(missing_or_stale_task(repo, id, versions).await)
             }
         }
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 // Delete a task for a given unique task id.
 pub async fn delete_task(
-    client: &Data<Client>,
+    repo: &dyn TaskRepository,
     path: Path<String>,
+    if_match: Option<IfMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         return Err(ApiErrorType::TaskNotFound);
     };
-    let result = task_repo::delete_task(client, &id).await;
+    let versions = if_match_versions(if_match);
+    let result = repo.delete_task(&id, versions.as_deref()).await;
     match result {
-        Ok(res) => {
-            if res.deleted_count == 1 {
+        Ok(matched) => {
+            if matched {
                 Ok(HttpResponse::NoContent().finish())
             } else {
-                warn!("Task with id -{} not found for delete task by ID", id);
-                Err(ApiErrorType::TaskNotFound)
+                Err(missing_or_stale_task(repo, &id, versions.as_deref()).await)
             }
         }
-        Err(err) => {
-            error!("Error : {}", err);
-            Err(ApiErrorType::InternalServerError)
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
+
+// Restore a task from the trash.
+pub async fn restore_task(
+    repo: &dyn TaskRepository,
+    path: Path<String>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let id = path.into_inner();
+    if id.is_empty() {
+        return Err(ApiErrorType::TaskNotFound);
+    };
+    let result = repo.restore_task(&id).await;
+    match result {
+        Ok(matched) => {
+            if matched {
+                let restored_task = repo.get_task(&id).await;
+                handle_optional_task_response(restored_task)
+            } else {
+                warn!("Task with id -{} not found in trash to restore", id);
+                Err(ApiErrorType::TaskNotFound)
+            }
         }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 // Fetch all tasks from the database
 pub async fn get_all_tasks(
-    client: &Data<Client>,
+    repo: &dyn TaskRepository,
+    req: &HttpRequest,
     pagination: &Pagination,
+    defaults: &PaginationSettings,
 ) -> Result<HttpResponse, ApiErrorType> {
-    let offset = pagination.offset.unwrap_or(constants::DEFAULT_OFFSET_SIZE);
-    let limit = pagination.limit.unwrap_or(constants::DEFAULT_LIMIT_SIZE);
-    let task_list = task_repo::get_all_tasks(client, offset, limit).await;
-    let task_count = task_repo::get_tasks_size(client).await.unwrap_or(0);
-    let last_offset = (task_count / (limit as u64)) * limit as u64;
-
-    let next_offset = i64::try_from(offset).unwrap_or(0) + limit;
-    let previous_offset = i64::try_from(offset).unwrap_or(0) - limit;
+    let limit = page_limit(pagination.limit, defaults)?;
+    if pagination.after.is_some() || pagination.before.is_some() {
+        return get_tasks_by_cursor(repo, req, pagination, limit).await;
+    }
+    let offset = pagination.offset.unwrap_or(defaults.default_offset);
+    let task_count = repo.get_tasks_size().await.unwrap_or(0);
+    // Offsets past the end have no page to link from.
+    if offset > task_count {
+        return Err(ApiErrorType::BadRequest);
+    }
+    let task_list = repo.get_all_tasks(offset, limit).await;
 
     match task_list {
         Ok(t) => {
-            let response = TaskListResponse {
-                data: t,
-                meta: Meta {
-                    offset,
-                    limit,
-                    total_results: task_count,
-                    search_criteria: None,
-                    sort_by: None,
-                },
-                _link: Link {
-                    first: LinkHref {
-                        href: format!("/api/tasks?offset={}&limit={}", 0, limit).to_string(),
-                    },
-                    last: LinkHref {
-                        href: format!("/api/tasks?offset={}&limit={}", last_offset, limit)
-                            .to_string(),
-                    },
-                    previous: if previous_offset < 0 {
-                        None
-                    } else {
-                        Some(LinkHref {
-                            href: format!("/api/tasks?offset={}&limit={}", previous_offset, limit)
-                                .to_string(),
-                        })
-                    },
-                    next: if (next_offset as u64) > last_offset {
-                        None
-                    } else {
-                        Some(LinkHref {
-                            href: format!("/api/tasks?offset={}&limit={}", next_offset, limit)
-                                .to_string(),
-                        })
-                    },
-                    self_link: LinkHref {
-                        href: format!("/api/tasks?offset={}&limit={}", offset, limit).to_string(),
-                    },
-                },
-            };
+            let response = PageBuilder::new(req, limit)
+                .offset(offset)
+                .total(Some(task_count))
+                .build(t);
             Ok(HttpResponse::Ok().json(response))
         }
-        Err(err) => {
-            error!("Error : {}", err);
-            Err(ApiErrorType::InternalServerError)
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
+
+// Fetch a page of tasks with keyset pagination.
+// Links carry cursors instead of offsets and the total is only counted on request.
+async fn get_tasks_by_cursor(
+    repo: &dyn TaskRepository,
+    req: &HttpRequest,
+    pagination: &Pagination,
+    limit: i64,
+) -> Result<HttpResponse, ApiErrorType> {
+    let keyset = Keyset::from_query(pagination.after.as_deref(), pagination.before.as_deref(), limit)?;
+    let task_list = repo
+        .get_tasks_by_cursor(keyset.cursor.as_ref(), keyset.backward, keyset.fetch_limit())
+        .await;
+    let tasks = match task_list {
+        Ok(t) => t,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
+
+    let task_count = if pagination.count.unwrap_or(false) {
+        Some(repo.get_tasks_size().await.unwrap_or(0))
+    } else {
+        None
+    };
+    let response = PageBuilder::new(req, limit)
+        .total(task_count)
+        .keyset(&keyset, tasks, |t| Cursor::new(&t.title, &t.id));
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Request index, task id, accepted versions and success status of a bulk write.
+type PendingWrite = (usize, Option<String>, Option<Vec<i64>>, StatusCode);
+
+// Run a batch of create, update and delete operations on tasks.
+// Every item is validated first. In atomic mode nothing is written unless every item succeeds.
+pub async fn bulk_tasks(
+    repo: &dyn TaskRepository,
+    request: BulkTaskRequest,
+    atomic: bool,
+    limits: &BulkSettings,
+    errors: &ErrorSettings,
+) -> Result<HttpResponse, ApiErrorType> {
+    if request.operations.len() > limits.max_operations {
+        warn!("Bulk request with {} operations rejected", request.operations.len());
+        return Err(ApiErrorType::BadRequest);
+    }
+
+    // Step 1: Validate every operation.
+    let mut results: Vec<Option<BulkItemResult>> = Vec::new();
+    let mut writes: Vec<TaskWrite> = Vec::new();
+    let mut pending: Vec<PendingWrite> = Vec::new();
+    for (index, operation) in request.operations.into_iter().enumerate() {
+        let (task, id, version) = match &operation {
+            BulkTaskOperation::Create { task } => (Some(task), None, None),
+            BulkTaskOperation::Update { id, task, version } => {
+                (Some(task), Some(id.clone()), *version)
+            }
+            BulkTaskOperation::Delete { id, version } => (None, Some(id.clone()), *version),
+        };
+        if let Some(Err(err)) = task.map(|t| t.validate()) {
+            warn!("Payload validation Error on bulk task {}: {}", index, err);
+            let error = ApiErrorType::ValidationError {
+                validation_error: err,
+                object: "Task".to_string(),
+            };
+            results.push(Some(BulkItemResult::failure(index, id, &error)));
+            continue;
         }
+        results.push(None);
+
+        let versions = version.map(|v| vec![v]);
+        let (write, status) = match operation {
+            BulkTaskOperation::Create { task } => (
+                TaskWrite::Create(Task {
+                    id: None,
+                    created_at: None,
+                    version: 0,
+                    ..task
+                }),
+                StatusCode::CREATED,
+            ),
+            BulkTaskOperation::Update { id, task, .. } => (
+                TaskWrite::Update {
+                    id,
+                    patch: TaskPatch::replace(task),
+                    versions: versions.clone(),
+                },
+                StatusCode::OK,
+            ),
+            BulkTaskOperation::Delete { id, .. } => (
+                TaskWrite::Delete {
+                    id,
+                    versions: versions.clone(),
+                },
+                StatusCode::NO_CONTENT,
+            ),
+        };
+        pending.push((index, id, versions, status));
+        writes.push(write);
+    }
+
+    // Step 2: Write to MongoDB. An invalid item fails the whole atomic batch up front.
+    let invalid = pending.len() < results.len();
+    let outcomes = if atomic && invalid {
+        writes.iter().map(|_| WriteOutcome::Aborted).collect()
+    } else if atomic {
+        match repo.bulk_write_atomic(writes).await {
+            Ok(outcomes) => outcomes,
+            Err(err) => return Err(ApiErrorType::internal(err)),
+        }
+    } else {
+        repo.bulk_write(writes).await
+    };
+
+    // Step 3: Report every item in request order.
+    for ((index, id, versions, status), outcome) in pending.into_iter().zip(outcomes) {
+        let result = match outcome {
+            WriteOutcome::Created(new_id) => BulkItemResult::success(index, Some(new_id), status),
+            WriteOutcome::Written => BulkItemResult::success(index, id, status),
+            WriteOutcome::NotMatched => {
+                let task_id = id.clone().unwrap_or_default();
+                let error = missing_or_stale_task(repo, &task_id, versions.as_deref()).await;
+                BulkItemResult::failure(index, id, &error)
+            }
+            WriteOutcome::Failed(err) => {
+                BulkItemResult::failure(index, id, &ApiErrorType::internal(err))
+            }
+            WriteOutcome::Aborted => BulkItemResult::failure(index, id, &ApiErrorType::BulkAborted),
+        };
+        results[index] = Some(result);
+    }
+
+    let mut results: Vec<BulkItemResult> = results.into_iter().flatten().collect();
+    if !errors.expose_details {
+        results.iter_mut().for_each(BulkItemResult::redact);
     }
+    Ok(HttpResponse::Ok().json(BulkResponse { atomic, results }))
 }
 
 fn handle_optional_task_response(
     task: Result<Option<Task>, Error>,
 ) -> Result<HttpResponse, ApiErrorType> {
     match task {
-        Ok(Some(task)) => Ok(HttpResponse::Ok().json(task)),
+        Ok(Some(task)) => Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag(task.version)))
+            .json(task)),
         Ok(None) => Err(ApiErrorType::TaskNotFound),
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
-// TaskService struct
-#[derive(Clone)] // Deriving Clone to fix the error
-pub struct TaskService {
-    repo: TaskRepository,
+// Stream every task as CSV or NDJSON straight from the Mongo cursor.
+pub async fn export_tasks(
+    repo: &dyn TaskRepository,
+    format: ExportFormat,
+) -> Result<HttpResponse, ApiErrorType> {
+    let cursor = match repo.export_tasks().await {
+        Ok(cursor) => cursor,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
+    let columns = ["id", "title", "body", "status", "tags", "assignee", "created_at"];
+    let rows = cursor.map(|task| task.map(TaskRecord::from));
+    Ok(export_service::export(rows, format, "tasks", &columns))
 }
 
-impl TaskService {
-    pub fn new (collection: Collection<Task>) -> Self {
-        Self {
-            repo: TaskRepository::new(collection),
+// Import tasks from a CSV or NDJSON upload.
+// Every row is validated like a new task. Valid rows are added even when others fail.
+pub async fn import_tasks(
+    repo: &dyn TaskRepository,
+    body: &[u8],
+    format: ExportFormat,
+    limits: &BulkSettings,
+    errors: &ErrorSettings,
+) -> Result<HttpResponse, ApiErrorType> {
+    let write = |tasks: Vec<Task>| async move {
+        let writes = tasks.into_iter().map(TaskWrite::Create).collect();
+        repo.bulk_write(writes)
+            .await
+            .into_iter()
+            .map(|outcome| match outcome {
+                WriteOutcome::Created(new_id) => Ok(new_id),
+                WriteOutcome::Failed(err) => Err(ApiErrorType::internal(err)),
+                _ => Err(ApiErrorType::internal("insert did not create a task")),
+            })
+            .collect()
+    };
+    export_service::import(body, format, "Task", limits, errors, TaskRecord::into_task, write).await
+}
+
+// A conditional write matched nothing: tell a stale version apart from a missing task.
+async fn missing_or_stale_task(
+    repo: &dyn TaskRepository,
+    id: &String,
+    versions: Option<&[i64]>,
+) -> ApiErrorType {
+    if versions.is_some() {
+        if let Ok(Some(_)) = repo.get_task(id).await {
+            warn!("Task with id -{} was modified since it was fetched", id);
+            return ApiErrorType::PreconditionFailed;
         }
     }
+    warn!("Task with id -{} not found to write task by ID", id);
+    ApiErrorType::TaskNotFound
+}
 
-    pub async fn aggregate_tasks(&self) -> Result<Vec<TaskAggregate>, mongodb::error::Error> {
-        let pipeline = vec![
-            doc! {"$group": {"_id": "$status", "count": {"$sum": 1}}},
-        ];
-
-        let cursor = self.repo.collection.aggregate(pipeline, None).await?;
-        let mut results = Vec::new();
-
-        while let Some(doc) = cursor.next().await {
-            match doc {
-                Ok(bson) => {
-                    if let Ok(agg) = bson::from_bson(Bson::Document(bson)) {
-                        results.push(agg);
-                    }
-                }
-                Err(e) => eprintln!("Error while aggregating: {}", e),
-            }
+// Task statistics for the tasks created between two dates, both included.
+// The range defaults to the last `stats.default_range_days` days.
+pub async fn task_stats(
+    repo: &dyn TaskRepository,
+    range: &StatsRange,
+    settings: &StatsSettings,
+) -> Result<HttpResponse, ApiErrorType> {
+    let (from, to) = match stats_dates(range, settings) {
+        Some(dates) => dates,
+        None => {
+            warn!("Invalid task stats range {:?} to {:?}", range.from, range.to);
+            return Err(ApiErrorType::BadRequest);
+        }
+    };
+    let end = to + Days::new(1);
+    match repo.task_stats(&from.to_string(), &end.to_string()).await {
+        Ok(mut stats) => {
+            // Report every day of the range, including the ones without tasks.
+            let mut created: HashMap<String, i64> = stats
+                .created_per_day
+                .drain(..)
+                .map(|day| (day.key, day.count))
+                .collect();
+            stats.created_per_day = from
+                .iter_days()
+                .take_while(|day| *day <= to)
+                .map(|day| {
+                    let key = day.to_string();
+                    let count = created.remove(&key).unwrap_or(0);
+                    StatCount { key, count }
+                })
+                .collect();
+            Ok(HttpResponse::Ok().json(TaskStatsResponse {
+                from: from.to_string(),
+                to: to.to_string(),
+                stats,
+            }))
         }
-        Ok(results)
+        Err(err) => Err(ApiErrorType::AggregatorError { source: err.into() }),
+    }
+}
+
+// Parse and check the stats range, `None` when it is invalid or too long.
+fn stats_dates(range: &StatsRange, settings: &StatsSettings) -> Option<(NaiveDate, NaiveDate)> {
+    let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+    let to = match &range.to {
+        Some(to) => parse(to)?,
+        None => DateTime::now().to_chrono().date_naive(),
+    };
+    let from = match &range.from {
+        Some(from) => parse(from)?,
+        None => to - Days::new(settings.default_range_days - 1),
+    };
+    let days = (to - from).num_days();
+    if days < 0 || days >= settings.max_range_days as i64 {
+        return None;
     }
+    Some((from, to))
 }
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use actix_web::{HttpRequest, HttpResponse};
+use log::{error, info};
+use mongodb::bson::DateTime;
+
+use crate::api::trash_api::TrashPagination;
+use crate::config::settings::PaginationSettings;
+use crate::models::error_model::ApiErrorType;
+use crate::models::page_response::{page_limit, PageBuilder};
+use crate::repository::trash_repo::TrashRepository;
+
+// Fetch a page of soft deleted tasks and users.
+pub async fn get_trash(
+    repo: &dyn TrashRepository,
+    req: &HttpRequest,
+    pagination: &TrashPagination,
+    defaults: &PaginationSettings,
+) -> Result<HttpResponse, ApiErrorType> {
+    let limit = page_limit(pagination.limit, defaults)?;
+    let offset = pagination.offset.unwrap_or(defaults.default_offset);
+    let trash_count = repo.get_trash_size().await.unwrap_or(0);
+    // Offsets past the end have no page to link from.
+    if offset > trash_count {
+        return Err(ApiErrorType::BadRequest);
+    }
+    let trash_list = repo.get_trash(offset, limit).await;
+
+    match trash_list {
+        Ok(items) => {
+            let response = PageBuilder::new(req, limit)
+                .offset(offset)
+                .total(Some(trash_count))
+                .build(items);
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
+
+// Permanently remove everything that has been in the trash longer than the retention period.
+pub async fn purge_expired(repo: &dyn TrashRepository, retention_days: u64) {
+    let retention = Duration::from_secs(retention_days * 24 * 60 * 60);
+    let cutoff = DateTime::from_system_time(std::time::SystemTime::now() - retention);
+    match repo.purge_trash(cutoff).await {
+        Ok(0) => {}
+        Ok(purged) => info!("Purged {} items deleted more than {} days ago", purged, retention_days),
+        Err(err) => error!("Error purging trash : {}", err),
+    }
+}
@@ -1,17 +1,26 @@
-use actix_web::web::{Data, Json, Path};
-use actix_web::HttpResponse;
-use log::{error, warn};
+use actix_web::web::{Json, Path};
+use actix_web::http::header::{ETag, IfMatch, IfNoneMatch};
+use actix_web::{HttpRequest, HttpResponse};
+use futures::stream::StreamExt;
+use log::warn;
 use mongodb::error::Error;
-use mongodb::Client;
 
 use crate::api::user_api::Pagination;
-use crate::constants;
-use crate::models::user_list_response::{Link, LinkHref, Meta, UserListResponse};
-use crate::{models::error_model::ApiErrorType, models::user_model::User, repository::user_repo};
+use crate::config::settings::{BulkSettings, ErrorSettings, PaginationSettings};
+use crate::models::cursor_model::Cursor;
+use crate::models::etag_model::{etag, if_match_versions, is_not_modified};
+use crate::models::export_model::ExportFormat;
+use crate::models::page_response::{page_limit, Keyset, PageBuilder};
+use crate::models::user_list_response::Users;
+use crate::models::user_model::UserPatch;
+use crate::services::export_service;
+use crate::{
+    models::error_model::ApiErrorType, models::user_model::User, repository::user_repo::UserRepository,
+};
 
 // add a new user to MongoDB
 pub async fn create_user(
-    client: &Data<Client>,
+    repo: &dyn UserRepository,
     new_user: Json<User>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let data = User {
@@ -19,161 +28,271 @@ pub async fn create_user(
         name: new_user.name.to_owned(),
         location: new_user.location.to_owned(),
         title: new_user.title.to_owned(),
+        version: 0,
     };
-    let user_detail = user_repo::create_user(client, data).await;
+    let user_detail = repo.create_user(data).await;
     match user_detail {
-        Ok(Some(user)) => Ok(HttpResponse::Created().json(user)),
-        Ok(None) => Err(ApiErrorType::InternalServerError),
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Ok(Some(user)) => Ok(HttpResponse::Created()
+            .insert_header(ETag(etag(user.version)))
+            .json(user)),
+        Ok(None) => Err(ApiErrorType::internal("created document not found")),
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 pub async fn get_user_by_id(
-    client: &Data<Client>,
+    repo: &dyn UserRepository,
     path: Path<String>,
+    if_none_match: Option<IfNoneMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         warn!("User with id - {} not found for get user by ID", id);
         return Err(ApiErrorType::BadRequest);
     }
-    let user_detail = user_repo::get_user(client, &id).await;
-    handle_optional_user_response(user_detail)
+    let user_detail = repo.get_user(&id).await;
+    match user_detail {
+        Ok(Some(user)) if is_not_modified(if_none_match, user.version) => {
+            Ok(HttpResponse::NotModified()
+                .insert_header(ETag(etag(user.version)))
+                .finish())
+        }
+        _ => handle_optional_user_response(user_detail),
+    }
 }
 
 pub async fn update_user(
-    client: &Data<Client>,
+    repo: &dyn UserRepository,
     path: Path<String>,
     update_user: Json<User>,
+    if_match: Option<IfMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         return Err(ApiErrorType::BadRequest);
     };
-    let data = User {
-        id: Some(String::from(&id)),
-        name: update_user.name.to_owned(),
-        location: update_user.location.to_owned(),
-        title: update_user.title.to_owned(),
+    let data = UserPatch {
+        name: Some(update_user.name.to_owned()),
+        location: Some(update_user.location.to_owned()),
+        title: Some(update_user.title.to_owned()),
     };
+    let versions = if_match_versions(if_match);
+    apply_user_patch(repo, &id, data, versions.as_deref()).await
+}
 
-    let update_result = user_repo::update_user(client, &id, data).await;
+// Partially update a user with a JSON merge patch.
+pub async fn patch_user(
+    repo: &dyn UserRepository,
+    path: Path<String>,
+    patch: UserPatch,
+    if_match: Option<IfMatch>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let id = path.into_inner();
+    if id.is_empty() {
+        return Err(ApiErrorType::BadRequest);
+    };
+    let versions = if_match_versions(if_match);
+    // Nothing to change, answer with the current user.
+    if patch.is_empty() {
+        let user_detail = repo.get_user(&id).await;
+        if let (Ok(Some(user)), Some(versions)) = (&user_detail, &versions) {
+            if !versions.contains(&user.version) {
+                return Err(ApiErrorType::PreconditionFailed);
+            }
+        }
+        return handle_optional_user_response(user_detail);
+    }
+    apply_user_patch(repo, &id, patch, versions.as_deref()).await
+}
+
+async fn apply_user_patch(
+    repo: &dyn UserRepository,
+    id: &String,
+    patch: UserPatch,
+    versions: Option<&[i64]>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let update_result = repo.update_user(id, &patch, versions).await;
     match update_result {
-        Ok(update) => {
-            if update.matched_count == 1 {
-                let updated_user_info = user_repo::get_user(client, &id).await;
+        Ok(matched) => {
+            if matched {
+                let updated_user_info = repo.get_user(id).await;
                 handle_optional_user_response(updated_user_info)
             } else {
-                warn!("User with id -{} not found update user by ID", id);
-                Err(ApiErrorType::UserNotFound)
+                Err(missing_or_stale_user(repo, id, versions).await)
             }
         }
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 pub async fn delete_user(
-    client: &Data<Client>,
+    repo: &dyn UserRepository,
     path: Path<String>,
+    if_match: Option<IfMatch>,
 ) -> Result<HttpResponse, ApiErrorType> {
     let id = path.into_inner();
     if id.is_empty() {
         return Err(ApiErrorType::UserNotFound);
     };
-    let result = user_repo::delete_user(client, &id).await;
+    let versions = if_match_versions(if_match);
+    let result = repo.delete_user(&id, versions.as_deref()).await;
     match result {
-        Ok(res) => {
-            if res.deleted_count == 1 {
+        Ok(matched) => {
+            if matched {
                 Ok(HttpResponse::NoContent().finish())
             } else {
-                warn!("User with id -{} not found for delete user by ID", id);
-                Err(ApiErrorType::UserNotFound)
+                Err(missing_or_stale_user(repo, &id, versions.as_deref()).await)
             }
         }
-        Err(err) => {
-            error!("Error : {}", err);
-            Err(ApiErrorType::InternalServerError)
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
+
+// Restore a user from the trash.
+pub async fn restore_user(
+    repo: &dyn UserRepository,
+    path: Path<String>,
+) -> Result<HttpResponse, ApiErrorType> {
+    let id = path.into_inner();
+    if id.is_empty() {
+        return Err(ApiErrorType::UserNotFound);
+    };
+    let result = repo.restore_user(&id).await;
+    match result {
+        Ok(matched) => {
+            if matched {
+                let restored_user = repo.get_user(&id).await;
+                handle_optional_user_response(restored_user)
+            } else {
+                warn!("User with id -{} not found in trash to restore", id);
+                Err(ApiErrorType::UserNotFound)
+            }
         }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
 pub async fn get_all_users(
-    client: &Data<Client>,
+    repo: &dyn UserRepository,
+    req: &HttpRequest,
     pagination: &Pagination,
+    defaults: &PaginationSettings,
 ) -> Result<HttpResponse, ApiErrorType> {
-    let offset = pagination.offset.unwrap_or(constants::DEFAULT_OFFSET_SIZE);
-    let limit = pagination.limit.unwrap_or(constants::DEFAULT_LIMIT_SIZE);
-    let user_list = user_repo::get_all_users(client, offset, limit).await;
-    let user_count = user_repo::get_users_size(client).await.unwrap_or(0);
-    let last_offset = (user_count / (limit as u64)) * limit as u64;
-
-    let next_offset = i64::try_from(offset).unwrap_or(0) + limit;
-    let previous_offset = i64::try_from(offset).unwrap_or(0) - limit;
+    let limit = page_limit(pagination.limit, defaults)?;
+    if pagination.after.is_some() || pagination.before.is_some() {
+        return get_users_by_cursor(repo, req, pagination, limit).await;
+    }
+    let offset = pagination.offset.unwrap_or(defaults.default_offset);
+    let user_count = repo.get_users_size().await.unwrap_or(0);
+    // Offsets past the end have no page to link from.
+    if offset > user_count {
+        return Err(ApiErrorType::BadRequest);
+    }
+    let user_list = repo.get_all_users(offset, limit).await;
 
     match user_list {
         Ok(u) => {
-            let response = UserListResponse {
-                data: u,
-                meta: Meta {
-                    offset,
-                    limit,
-                    total_results: user_count,
-                    search_criteria: None,
-                    sort_by: None,
-                },
-                _link: Link {
-                    first: LinkHref {
-                        href: format!("/api/users?offset={}&limit={}", 0, limit).to_string(),
-                    },
-                    last: LinkHref {
-                        href: format!("/api/users?offset={}&limit={}", last_offset, limit)
-                            .to_string(),
-                    },
-                    previous: if previous_offset < 0 {
-                        None
-                    } else {
-                        Some(LinkHref {
-                            href: format!("/api/users?offset={}&limit={}", previous_offset, limit)
-                                .to_string(),
-                        })
-                    },
-                    next: if (next_offset as u64) > last_offset {
-                        None
-                    } else {
-                        Some(LinkHref {
-                            href: format!("/api/users?offset={}&limit={}", next_offset, limit)
-                                .to_string(),
-                        })
-                    },
-                    self_link: LinkHref {
-                        href: format!("/api/users?offset={}&limit={}", offset, limit).to_string(),
-                    },
-                },
-            };
+            let response = PageBuilder::new(req, limit)
+                .offset(offset)
+                .total(Some(user_count))
+                .build(u);
             Ok(HttpResponse::Ok().json(response))
         }
-        Err(err) => {
-            error!("Error : {}", err);
-            Err(ApiErrorType::InternalServerError)
-        }
+        Err(err) => Err(ApiErrorType::internal(err)),
     }
 }
 
+// Fetch a page of users with keyset pagination.
+// Links carry cursors instead of offsets and the total is only counted on request.
+async fn get_users_by_cursor(
+    repo: &dyn UserRepository,
+    req: &HttpRequest,
+    pagination: &Pagination,
+    limit: i64,
+) -> Result<HttpResponse, ApiErrorType> {
+    let keyset = Keyset::from_query(pagination.after.as_deref(), pagination.before.as_deref(), limit)?;
+    let user_list = repo
+        .get_users_by_cursor(keyset.cursor.as_ref(), keyset.backward, keyset.fetch_limit())
+        .await;
+    let users = match user_list {
+        Ok(u) => u,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
+
+    let user_count = if pagination.count.unwrap_or(false) {
+        Some(repo.get_users_size().await.unwrap_or(0))
+    } else {
+        None
+    };
+    let response = PageBuilder::new(req, limit)
+        .total(user_count)
+        .keyset(&keyset, users, |u| Cursor::new(&u.name, &u.id));
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Stream every user as CSV or NDJSON straight from the Mongo cursor.
+pub async fn export_users(
+    repo: &dyn UserRepository,
+    format: ExportFormat,
+) -> Result<HttpResponse, ApiErrorType> {
+    let cursor = match repo.export_users().await {
+        Ok(cursor) => cursor,
+        Err(err) => return Err(ApiErrorType::internal(err)),
+    };
+    let rows = cursor.map(|user| {
+        user.map(|user| Users {
+            id: user.id.unwrap_or_default(),
+            name: user.name,
+            location: user.location,
+            title: user.title,
+        })
+    });
+    Ok(export_service::export(rows, format, "users", &["id", "name", "location", "title"]))
+}
+
+// Import users from a CSV or NDJSON upload.
+// Every row is validated like a new user. Valid rows are added even when others fail.
+pub async fn import_users(
+    repo: &dyn UserRepository,
+    body: &[u8],
+    format: ExportFormat,
+    limits: &BulkSettings,
+    errors: &ErrorSettings,
+) -> Result<HttpResponse, ApiErrorType> {
+    let write = |users: Vec<User>| async move {
+        repo.create_users(users)
+            .await
+            .into_iter()
+            .map(|outcome| outcome.map_err(ApiErrorType::internal))
+            .collect()
+    };
+    export_service::import(body, format, "User", limits, errors, Ok::<User, String>, write).await
+}
+
 fn handle_optional_user_response(
     user: Result<Option<User>, Error>,
 ) -> Result<HttpResponse, ApiErrorType> {
     match user {
-        Ok(Some(user)) => Ok(HttpResponse::Ok().json(user)),
+        Ok(Some(user)) => Ok(HttpResponse::Ok()
+            .insert_header(ETag(etag(user.version)))
+            .json(user)),
         Ok(None) => Err(ApiErrorType::UserNotFound),
-        Err(err) => {
-            error!("Error: {}", err);
-            Err(ApiErrorType::InternalServerError)
+        Err(err) => Err(ApiErrorType::internal(err)),
+    }
+}
+
+// A conditional write matched nothing: tell a stale version apart from a missing user.
+async fn missing_or_stale_user(
+    repo: &dyn UserRepository,
+    id: &String,
+    versions: Option<&[i64]>,
+) -> ApiErrorType {
+    if versions.is_some() {
+        if let Ok(Some(_)) = repo.get_user(id).await {
+            warn!("User with id -{} was modified since it was fetched", id);
+            return ApiErrorType::PreconditionFailed;
         }
     }
+    warn!("User with id -{} not found to write user by ID", id);
+    ApiErrorType::UserNotFound
 }
@@ -0,0 +1,236 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::task::Poll;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use log::info;
+use opentelemetry::global::{self, BoxedSpan};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, TraceError, Tracer};
+use opentelemetry::{Context, ContextGuard, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+
+use crate::config::settings::OtelSettings;
+
+const TRACER: &str = env!("CARGO_PKG_NAME");
+
+// Build the tracer provider from the settings and install it.
+// No provider is built with the `none` exporter, spans are then dropped
+// but `traceparent` headers are still passed on to outbound calls.
+pub fn init(settings: &OtelSettings) -> Result<Option<TracerProvider>, TraceError> {
+    let resource = Resource::new([KeyValue::new("service.name", settings.service_name.clone())]);
+
+    let provider = match settings.exporter.as_str() {
+        "otlp" => {
+            info!("Exporting traces to {}", settings.endpoint);
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(&settings.endpoint)
+                .build()?;
+            TracerProvider::builder()
+                .with_batch_exporter(exporter, runtime::TokioCurrentThread)
+                .with_resource(resource)
+                .build()
+        }
+        "stdout" => TracerProvider::builder()
+            .with_simple_exporter(opentelemetry_stdout::SpanExporter::default())
+            .with_resource(resource)
+            .build(),
+        "none" => {
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            return Ok(None);
+        }
+        other => return Err(TraceError::from(format!("unknown otel exporter {}", other))),
+    };
+    install(provider.clone());
+    Ok(Some(provider))
+}
+
+// Make the provider global, with W3C trace context propagation.
+pub fn install(provider: TracerProvider) {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider);
+}
+
+// A span for a repository function, child of the current span, ended when dropped.
+pub fn span(repository: &str, operation: &str) -> BoxedSpan {
+    global::tracer(TRACER).start_with_context(format!("{}.{}", repository, operation), &Context::current())
+}
+
+// Run a future in a child span of the current one, named after the service function.
+pub fn traced<F: Future>(name: &'static str, future: F) -> Traced<F> {
+    let span = global::tracer(TRACER).start_with_context(name, &Context::current());
+    Traced::new(Context::current_with_span(span), future)
+}
+
+// Headers carrying the current trace to an outbound call.
+pub fn trace_headers() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+// A future polled with its context attached, so spans started inside it are children
+// of its span and log lines carry its trace id.
+pub struct Traced<F> {
+    future: Pin<Box<F>>,
+    cx: Context,
+}
+
+impl<F> Traced<F> {
+    pub fn new(cx: Context, future: F) -> Self {
+        Self { future: Box::pin(future), cx }
+    }
+}
+
+impl<F: Future> Future for Traced<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, task: &mut std::task::Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        let _attached = Attached::new(&this.cx);
+        this.future.as_mut().poll(task)
+    }
+}
+
+// Attaches a context and puts its ids in the log4rs MDC, both restored when dropped.
+struct Attached {
+    guard: Option<ContextGuard>,
+}
+
+impl Attached {
+    fn new(cx: &Context) -> Self {
+        let guard = cx.clone().attach();
+        sync_log_ids();
+        Self { guard: Some(guard) }
+    }
+}
+
+impl Drop for Attached {
+    fn drop(&mut self) {
+        self.guard.take();
+        sync_log_ids();
+    }
+}
+
+// `trace_id` and `span_id` of the current span for log lines, removed outside of a trace.
+fn sync_log_ids() {
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    if span_context.is_valid() {
+        log_mdc::insert("trace_id", span_context.trace_id().to_string());
+        log_mdc::insert("span_id", span_context.span_id().to_string());
+    } else {
+        log_mdc::remove("trace_id");
+        log_mdc::remove("span_id");
+    }
+}
+
+struct HeaderExtractor<'a>(&'a actix_web::http::header::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+// Middleware starting a server span for every request, continuing the trace
+// of an inbound `traceparent` header. Named after the route pattern like the metrics.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(req.headers()))
+        });
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let tracer = global::tracer(TRACER);
+        let span = tracer
+            .span_builder(format!("{} {}", method, route))
+            .with_kind(SpanKind::Server)
+            .with_attributes([
+                KeyValue::new("http.request.method", method),
+                KeyValue::new("http.route", route),
+                KeyValue::new("url.path", req.path().to_owned()),
+            ])
+            .start_with_context(&tracer, &parent);
+        let cx = parent.with_span(span);
+
+        let future = {
+            let _attached = Attached::new(&cx);
+            self.service.call(req)
+        };
+        Box::pin(async move {
+            let result = Traced::new(cx.clone(), future).await;
+            let status = match &result {
+                Ok(response) => response.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            let span = cx.span();
+            span.set_attribute(KeyValue::new("http.response.status_code", i64::from(status.as_u16())));
+            if status.is_server_error() {
+                span.set_status(Status::error(status.to_string()));
+            }
+            span.end();
+            result
+        })
+    }
+}
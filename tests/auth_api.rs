@@ -0,0 +1,195 @@
+mod common;
+
+use std::io;
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::models::auth_model::Auth;
+use actix_api::repository::auth_repo::AuthRepository;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use async_trait::async_trait;
+use mongodb::error::Error;
+use serde_json::json;
+
+use common::read_json;
+
+// Auth collection on a database that is down.
+struct Unreachable;
+
+fn refused() -> Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, "mongo-0.internal:27017 refused the connection").into()
+}
+
+#[async_trait]
+impl AuthRepository for Unreachable {
+    async fn auth_register(&self, _register_user: Auth) -> Result<bool, Error> {
+        Err(refused())
+    }
+
+    async fn check_email(&self, _email: &str) -> Result<bool, Error> {
+        Err(refused())
+    }
+
+    async fn fetch_by_email(&self, _email: &str) -> Result<Option<Auth>, Error> {
+        Err(refused())
+    }
+}
+
+fn register_payload() -> serde_json::Value {
+    json!({
+        "email": "jane@c12.io",
+        "first_name": "Jane",
+        "last_name": "Doe",
+        "password": "correct-horse-battery",
+    })
+}
+
+#[actix_web::test]
+async fn register_then_login_returns_a_token_for_the_api() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/a/register")
+        .set_json(register_payload())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(read_json(resp).await["status"], "Success");
+
+    // The same email can not register twice.
+    let req = test::TestRequest::post()
+        .uri("/a/register")
+        .set_json(register_payload())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(read_json(resp).await["status"], "Failed");
+
+    let req = test::TestRequest::post()
+        .uri("/a/login")
+        .set_json(json!({"email": "jane@c12.io", "password": "correct-horse-battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = read_json(resp).await;
+    assert_eq!(body["token_type"], "Bearer");
+    let token = body["access_token"].as_str().unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn login_with_wrong_credentials_is_unauthorized() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/a/register")
+        .set_json(register_payload())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::post()
+        .uri("/a/login")
+        .set_json(json!({"email": "jane@c12.io", "password": "not-the-right-one"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body = read_json(resp).await;
+    assert_eq!(body["status"], 401);
+    assert_eq!(body["message"], "Invalid credential.");
+
+    let req = test::TestRequest::post()
+        .uri("/a/login")
+        .set_json(json!({"email": "nobody@c12.io", "password": "correct-horse-battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn register_with_invalid_payload_is_a_bad_request() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/a/register")
+        .set_json(json!({
+            "email": "not-an-email",
+            "first_name": "Jane",
+            "last_name": "Doe",
+            "password": "short",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(read_json(resp).await["status"], 400);
+}
+
+#[actix_web::test]
+async fn api_routes_require_a_valid_token() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    // Without an Authorization header the bearer middleware refuses the request.
+    let req = test::TestRequest::get().uri("/api/tasks").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body = read_json(resp).await;
+    assert_eq!(body["status"], 401);
+    assert_eq!(body["message"], "Authentication error.");
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks")
+        .insert_header((header::AUTHORIZATION, "Bearer not-a-jwt"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // A valid token without the USER role is rejected by the role check.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks")
+        .insert_header(common::bearer(&["ROLE_GUEST"]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn unsecured_routes_answer_without_a_token() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get().uri("/ping").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("X-Version").unwrap(), "0.3.0");
+
+    let req = test::TestRequest::get().uri("/no-such-route").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn storage_errors_are_internal_errors() {
+    let repos = Repositories {
+        auth: Arc::new(Unreachable),
+        ..Repositories::in_memory()
+    };
+    let app = test::init_service(create_app(Default::default(), repos)).await;
+
+    let requests = [
+        ("/a/register", register_payload()),
+        ("/a/login", json!({"email": "jane@c12.io", "password": "correct-horse-battery"})),
+    ];
+    for (uri, payload) in requests {
+        let req = test::TestRequest::post().uri(uri).set_json(payload).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = read_json(resp).await;
+        assert!(body["error_id"].is_string());
+        assert!(body["debug_message"].is_null());
+    }
+}
@@ -0,0 +1,44 @@
+// Helpers shared by the integration tests.
+// Every test boots the same app as `main`, on the in-memory repositories.
+#![allow(dead_code)]
+
+use actix_api::auth::claims::Claims;
+use actix_api::config::settings::JwtSettings;
+use actix_api::models::auth_model::Auth;
+use actix_web::body::MessageBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use actix_web::test;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+// Bearer header for a user with the given roles, without going through register and login.
+pub fn bearer(roles: &[&str]) -> (header::HeaderName, String) {
+    account_bearer("test-user", roles)
+}
+
+pub fn account_bearer(id: &str, roles: &[&str]) -> (header::HeaderName, String) {
+    let auth = Auth {
+        id: id.to_owned(),
+        email: "test@c12.io".to_owned(),
+        first_name: "Test".to_owned(),
+        last_name: "User".to_owned(),
+        password_hash: String::new(),
+        roles: roles.iter().map(|r| r.to_string()).collect(),
+        active: true,
+        reset_password: false,
+        created_ts: DateTime::<Utc>::MIN_UTC,
+        updated_ts: DateTime::<Utc>::MIN_UTC,
+    };
+    let token = Claims::create_jwt_token(&auth, &JwtSettings::default()).unwrap().access_token;
+    (header::AUTHORIZATION, format!("Bearer {}", token))
+}
+
+pub fn user_bearer() -> (header::HeaderName, String) {
+    bearer(&["ROLE_USER"])
+}
+
+pub async fn read_json(resp: ServiceResponse<impl MessageBody>) -> Value {
+    let body = test::read_body(resp).await;
+    serde_json::from_slice(&body).unwrap()
+}
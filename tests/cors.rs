@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::{Settings, SettingsError};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test;
+
+fn settings(vars: &[(&str, &str)]) -> Result<Settings, SettingsError> {
+    let mut vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    vars.insert("APP__MONGO__URI".to_owned(), "mongodb://localhost:27017".to_owned());
+    Settings::build(None, Some(vars))
+}
+
+fn preflight(uri: &str, origin: &str) -> test::TestRequest {
+    test::TestRequest::default()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .insert_header((header::ORIGIN, origin))
+        .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+}
+
+#[actix_web::test]
+async fn scopes_apply_their_own_policy() {
+    let settings = settings(&[
+        ("APP__CORS__API__ALLOWED_ORIGINS", "https://*.c12.io"),
+        ("APP__CORS__API__ALLOW_CREDENTIALS", "true"),
+    ])
+    .unwrap();
+    let app = test::init_service(create_app(Arc::new(settings), Repositories::in_memory())).await;
+
+    // Preflights to `/api` carry no token and are answered by the scope policy.
+    let resp = test::call_service(&app, preflight("/api/tasks", "https://app.c12.io").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let headers = resp.headers();
+    assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://app.c12.io");
+    assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+
+    // The bare domain does not match the subdomain pattern.
+    let resp = test::call_service(&app, preflight("/api/tasks", "https://c12.io").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // `/a` keeps the global policy.
+    let resp = test::call_service(&app, preflight("/a/login", "https://app.c12.io").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, preflight("/a/login", "http://localhost:8080").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "http://localhost:8080"
+    );
+    assert!(resp.headers().get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+}
+
+#[actix_web::test]
+async fn simple_requests_expose_the_configured_headers() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/health/live")
+        .insert_header((header::ORIGIN, "http://127.0.0.1:8080"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let exposed = resp.headers().get(header::ACCESS_CONTROL_EXPOSE_HEADERS).unwrap().to_str().unwrap();
+    assert!(exposed.contains("etag"));
+    assert!(exposed.contains("x-request-id"));
+}
+
+#[actix_web::test]
+async fn invalid_policies_are_rejected() {
+    let err = settings(&[
+        ("APP__CORS__AUTH__ALLOWED_ORIGINS", "*"),
+        ("APP__CORS__AUTH__ALLOW_CREDENTIALS", "true"),
+        ("APP__CORS__API__ALLOWED_ORIGINS", "app.c12.io"),
+        ("APP__CORS__ALLOWED_METHODS", "GET,NOT A METHOD"),
+    ])
+    .unwrap_err();
+
+    let SettingsError::Invalid { errors } = err else {
+        panic!("expected validation errors, got {}", err);
+    };
+    assert!(errors.contains(&"cors.auth: credentials can not be allowed for any origin (*)".to_owned()));
+    assert!(errors.contains(&"cors.api: app.c12.io is not an origin or a *.domain pattern".to_owned()));
+    assert!(errors.contains(&"cors.global: NOT A METHOD is not an HTTP method".to_owned()));
+}
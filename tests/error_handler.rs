@@ -0,0 +1,166 @@
+use std::io;
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::Settings;
+use actix_api::models::trash_model::TrashItem;
+use actix_api::repository::trash_repo::TrashRepository;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use async_trait::async_trait;
+use mongodb::bson::DateTime;
+use mongodb::error::Error;
+
+mod common;
+
+// Trash collection on a database that is down.
+struct Unreachable;
+
+fn refused() -> Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, "mongo-0.internal:27017 refused the connection").into()
+}
+
+#[async_trait]
+impl TrashRepository for Unreachable {
+    async fn get_trash(&self, _offset: u64, _limit: i64) -> Result<Vec<TrashItem>, Error> {
+        Err(refused())
+    }
+
+    async fn get_trash_size(&self) -> Result<u64, Error> {
+        Err(refused())
+    }
+
+    async fn purge_trash(&self, _cutoff: DateTime) -> Result<u64, Error> {
+        Err(refused())
+    }
+}
+
+fn exposing_details() -> Arc<Settings> {
+    let vars = [
+        ("APP__MONGO__URI".to_owned(), "mongodb://localhost:27017".to_owned()),
+        ("APP__ERRORS__EXPOSE_DETAILS".to_owned(), "true".to_owned()),
+    ];
+    Arc::new(Settings::build(None, Some(vars.into_iter().collect())).unwrap())
+}
+
+#[actix_web::test]
+async fn framework_errors_use_the_api_error_shape() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get().uri("/nowhere").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+    let body = common::read_json(resp).await;
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["message"], "Resource not found.");
+    assert!(body["request_id"].is_string());
+
+    let req = test::TestRequest::post()
+        .uri("/api/aggregate")
+        .insert_header(common::user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(common::read_json(resp).await["message"], "Method not allowed.");
+}
+
+#[actix_web::test]
+async fn auth_failures_use_the_api_error_shape() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    // An invalid token is refused by the bearer middleware.
+    let req = test::TestRequest::get()
+        .uri("/api/hello")
+        .insert_header((header::AUTHORIZATION, "Bearer not-a-jwt"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["status"], 401);
+    assert_eq!(body["message"], "Authentication error.");
+
+    // A valid token without the role is denied by the grants.
+    let req = test::TestRequest::get()
+        .uri("/api/trash")
+        .insert_header(common::bearer(&[]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["status"], 403);
+    assert_eq!(body["message"], "Authorization error.");
+    assert!(body["debug_message"].is_null());
+}
+
+#[actix_web::test]
+async fn api_errors_are_left_untouched() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/unknown-id")
+        .insert_header(common::user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["message"], "Task not found for the given ID");
+}
+
+// Extractor failures keep their explanation as the debug message, sent only when enabled.
+#[actix_web::test]
+async fn debug_messages_are_only_sent_when_exposed() {
+    let req = || {
+        test::TestRequest::get()
+            .uri("/api/trash?limit=many")
+            .insert_header(common::user_bearer())
+            .to_request()
+    };
+
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+    let resp = test::call_service(&app, req()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["message"], "Bad request.");
+    assert!(body["debug_message"].is_null());
+
+    let app = test::init_service(create_app(exposing_details(), Repositories::in_memory())).await;
+    let body = common::read_json(test::call_service(&app, req()).await).await;
+    assert!(body["debug_message"].as_str().unwrap().starts_with("Query deserialize error"));
+}
+
+#[actix_web::test]
+async fn internal_errors_only_return_an_error_id() {
+    let req = |accept: &str| {
+        test::TestRequest::get()
+            .uri("/api/trash")
+            .insert_header(common::user_bearer())
+            .insert_header((header::ACCEPT, accept.to_owned()))
+            .to_request()
+    };
+    let repos = || Repositories {
+        trash: Arc::new(Unreachable),
+        ..Repositories::in_memory()
+    };
+
+    let app = test::init_service(create_app(Default::default(), repos())).await;
+    let resp = test::call_service(&app, req("application/json")).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = test::read_body(resp).await;
+    assert!(!String::from_utf8_lossy(&body).contains("mongo-0.internal"));
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["message"], "Internal server error. Try again after some time.");
+    assert!(body["debug_message"].is_null());
+    assert!(body["error_id"].is_string());
+
+    let resp = test::call_service(&app, req("application/problem+json")).await;
+    let body = common::read_json(resp).await;
+    assert_eq!(body["type"], "https://c12.io/problems/internal-server-error");
+    assert!(body["error_id"].is_string());
+    assert!(body.get("detail").is_none());
+
+    // The cause is in the debug message once details are exposed.
+    let app = test::init_service(create_app(exposing_details(), repos())).await;
+    let body = common::read_json(test::call_service(&app, req("application/json")).await).await;
+    assert!(body["debug_message"].as_str().unwrap().contains("mongo-0.internal:27017 refused the connection"));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::Settings;
+use actix_api::services::health_service::HealthCheck;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use async_trait::async_trait;
+use serde_json::json;
+
+use common::read_json;
+
+struct StaticCheck {
+    name: &'static str,
+    required: bool,
+    healthy: bool,
+}
+
+#[async_trait]
+impl HealthCheck for StaticCheck {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        if self.healthy {
+            Ok(())
+        } else {
+            Err("connection refused".to_owned())
+        }
+    }
+}
+
+#[actix_web::test]
+async fn live_and_ready_without_dependencies() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for uri in ["/health/live", "/health/ready"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = read_json(resp).await;
+        assert_eq!(body["status"], "up");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    }
+}
+
+#[actix_web::test]
+async fn ready_is_unavailable_when_a_required_check_fails() {
+    let mut repos = Repositories::in_memory();
+    repos.health.register(StaticCheck { name: "cache", required: false, healthy: false });
+    let app = test::init_service(create_app(Default::default(), repos.clone())).await;
+
+    // An optional dependency being down is reported but keeps the app ready.
+    let req = test::TestRequest::get().uri("/health/ready").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = read_json(resp).await;
+    assert_eq!(body["checks"][0]["status"], "down");
+    assert!(body["checks"][0]["error_id"].is_string());
+    assert!(body["checks"][0].get("error").is_none());
+
+    repos.health.register(StaticCheck { name: "mongodb", required: true, healthy: false });
+    let app = test::init_service(create_app(Default::default(), repos)).await;
+
+    let req = test::TestRequest::get().uri("/health/ready").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = read_json(resp).await;
+    assert_eq!(body["status"], "down");
+    assert_eq!(body["checks"][1]["name"], "mongodb");
+    assert_eq!(body["checks"][1]["required"], json!(true));
+    assert!(body["checks"][1]["latency_ms"].is_u64());
+
+    // Liveness does not depend on the checks.
+    let req = test::TestRequest::get().uri("/health/live").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn check_errors_are_only_sent_when_exposed() {
+    let mut repos = Repositories::in_memory();
+    repos.health.register(StaticCheck { name: "mongodb", required: true, healthy: false });
+    let mut settings = Settings::default();
+    settings.errors.expose_details = true;
+    let app = test::init_service(create_app(Arc::new(settings), repos)).await;
+
+    let req = test::TestRequest::get().uri("/health/ready").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = read_json(resp).await;
+    assert_eq!(body["checks"][0]["error"], "connection refused");
+    assert!(body["checks"][0]["error_id"].is_string());
+}
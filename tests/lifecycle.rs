@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::ShutdownSettings;
+use actix_api::lifecycle::Lifecycle;
+use actix_web::http::StatusCode;
+use actix_web::rt::time::sleep;
+use actix_web::{test, web, App, HttpServer};
+use serde_json::Value;
+
+fn lifecycle(grace_period_secs: u64) -> Lifecycle {
+    Lifecycle::new(&ShutdownSettings {
+        grace_period_secs,
+        drain_delay_secs: 0,
+    })
+}
+
+#[actix_web::test]
+async fn readiness_fails_once_shutdown_starts() {
+    let lifecycle = lifecycle(1);
+    let mut repos = Repositories::in_memory();
+    repos.health.register(lifecycle.shutdown());
+    let app = test::init_service(create_app(Default::default(), repos)).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/health/ready").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    lifecycle.shutdown().start();
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/health/ready").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["checks"][0]["name"], "shutdown");
+    assert_eq!(body["checks"][0]["status"], "down");
+    assert!(body["checks"][0]["error_id"].is_string());
+
+    // Liveness is unaffected, the process still serves in-flight requests.
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/health/live").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn jobs_finish_within_the_grace_period() {
+    let mut lifecycle = lifecycle(1);
+    let finished = Arc::new(AtomicBool::new(false));
+    let cooperative = finished.clone();
+    lifecycle.spawn("cooperative", move |shutdown| async move {
+        while shutdown.until(sleep(Duration::from_millis(10))).await.is_some() {}
+        // Work still in progress when the signal arrives completes.
+        sleep(Duration::from_millis(50)).await;
+        cooperative.store(true, Ordering::SeqCst);
+    });
+    let stubborn = Arc::new(AtomicBool::new(false));
+    let ignored = stubborn.clone();
+    lifecycle.spawn("stubborn", move |_| async move {
+        sleep(Duration::from_secs(60)).await;
+        ignored.store(true, Ordering::SeqCst);
+    });
+
+    let started = Instant::now();
+    lifecycle.drain().await;
+    assert!(finished.load(Ordering::SeqCst));
+    assert!(!stubborn.load(Ordering::SeqCst));
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(5));
+}
+
+#[actix_web::test]
+async fn in_flight_requests_complete_on_shutdown() {
+    let lifecycle = lifecycle(5);
+    let shutdown = lifecycle.shutdown();
+    let server = HttpServer::new(|| {
+        App::new().route(
+            "/slow",
+            web::get().to(|| async {
+                sleep(Duration::from_millis(300)).await;
+                "done"
+            }),
+        )
+    })
+    .workers(1)
+    .disable_signals()
+    .shutdown_timeout(5)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let running = actix_web::rt::spawn(lifecycle.run(server.run()));
+
+    let request = actix_web::rt::spawn(async move {
+        let resp = reqwest::get(format!("http://{}/slow", addr)).await.unwrap();
+        (resp.status().as_u16(), resp.text().await.unwrap())
+    });
+    sleep(Duration::from_millis(100)).await;
+    shutdown.start();
+
+    assert_eq!(request.await.unwrap(), (200, "done".to_owned()));
+    running.await.unwrap().unwrap();
+}
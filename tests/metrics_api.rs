@@ -0,0 +1,77 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::Settings;
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App, HttpServer};
+use serde_json::json;
+
+use common::{read_json, user_bearer};
+
+#[actix_web::test]
+async fn requests_are_counted_by_route_pattern() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for id in ["first-missing-task", "second-missing-task"] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/tasks/{}", id))
+            .insert_header(user_bearer())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains(
+        r#"http_requests_total{method="GET",route="/api/tasks/{id}",status="404"} 2"#
+    ));
+    assert!(body.contains("http_request_duration_seconds_bucket"));
+    assert!(!body.contains("first-missing-task"));
+}
+
+#[actix_web::test]
+async fn aggregator_calls_are_counted_as_outbound_requests() {
+    // Local stand-in for the upstream, on a free port.
+    let upstream = HttpServer::new(|| {
+        App::new()
+            .route("/data1", web::get().to(|| async { "first" }))
+            .route("/data2", web::get().to(|| async { "second" }))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = upstream.addrs()[0];
+    let upstream = upstream.run();
+    let handle = upstream.handle();
+    actix_web::rt::spawn(upstream);
+
+    let mut settings = Settings::default();
+    settings.aggregator.base_url = format!("http://{}", addr);
+    let app = test::init_service(create_app(Arc::new(settings), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/aggregate")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_json(resp).await, json!({"data1": "first", "data2": "second"}));
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let resp = test::call_service(&app, req).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains(r#"outbound_http_requests_total{service="aggregator_service",status="200"} 2"#));
+    handle.stop(false).await;
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::Settings;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::json;
+
+mod common;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+#[actix_web::test]
+async fn problem_details_are_negotiated_with_accept() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/unknown-id")
+        .insert_header(common::user_bearer())
+        .insert_header((header::ACCEPT, "application/problem+json, application/json;q=0.5"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+    assert!(resp.headers().get_all(header::VARY).any(|vary| vary == "accept"));
+    let body = common::read_json(resp).await;
+    assert_eq!(body["type"], "https://c12.io/problems/task-not-found");
+    assert_eq!(body["title"], "Task not found for the given ID");
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["instance"], "/api/tasks/unknown-id");
+    assert!(body["request_id"].is_string());
+
+    // Framework errors are rendered the same way.
+    let req = test::TestRequest::get()
+        .uri("/nowhere")
+        .insert_header((header::ACCEPT, PROBLEM_JSON))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body = common::read_json(resp).await;
+    assert_eq!(body["type"], "https://c12.io/problems/route-not-found");
+    assert_eq!(body["instance"], "/nowhere");
+
+    // Existing clients keep the ApiError shape.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/unknown-id")
+        .insert_header(common::user_bearer())
+        .insert_header((header::ACCEPT, "application/json"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+    let body = common::read_json(resp).await;
+    assert_eq!(body["message"], "Task not found for the given ID");
+    assert!(body.get("type").is_none());
+}
+
+#[actix_web::test]
+async fn validation_errors_are_an_extension_member() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(common::user_bearer())
+        .insert_header((header::ACCEPT, PROBLEM_JSON))
+        .set_json(json!({"title": "Tiny", "body": "Cover every route of the API"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["type"], "https://c12.io/problems/validation-error");
+    assert_eq!(body["errors"][0]["field"], "title");
+    assert_eq!(body["errors"][0]["message"], "Title must have minimum of 5 characters");
+}
+
+#[actix_web::test]
+async fn problem_format_can_be_the_default() {
+    let vars = [
+        ("APP__MONGO__URI", "mongodb://localhost:27017"),
+        ("APP__ERRORS__FORMAT", "problem"),
+        ("APP__ERRORS__TYPE_BASE_URI", "https://errors.example.com/v1/"),
+    ];
+    let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let settings = Settings::build(None, Some(vars)).unwrap();
+    let app = test::init_service(create_app(Arc::new(settings), Repositories::in_memory())).await;
+
+    // Malformed payloads are reported by the JSON extractor.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(common::user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload("{")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+    let body = common::read_json(resp).await;
+    assert_eq!(body["type"], "https://errors.example.com/v1/bad-request");
+    assert!(body.get("detail").is_none());
+
+    // An explicit `application/json` keeps the ApiError shape.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/unknown-id")
+        .insert_header(common::user_bearer())
+        .insert_header((header::ACCEPT, "application/json"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+    let body = common::read_json(resp).await;
+    assert_eq!(body["message"], "Task not found for the given ID");
+    assert!(body.get("type").is_none());
+
+    // Wildcards leave the choice to the configuration.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/unknown-id")
+        .insert_header(common::user_bearer())
+        .insert_header((header::ACCEPT, "*/*"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+
+    assert!(Settings::build(
+        None,
+        Some([("APP__ERRORS__FORMAT".to_owned(), "xml".to_owned())].into_iter().collect())
+    )
+    .is_err());
+}
@@ -0,0 +1,140 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::config::settings::{RateLimitRule, Settings, SettingsError};
+use actix_api::rate_limit::IpRange;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::json;
+
+mod common;
+
+fn settings(route: &str, key: &str, requests: u32) -> Arc<Settings> {
+    let mut settings = Settings::default();
+    settings.rate_limit.trusted_proxies = vec!["127.0.0.1".to_owned(), "10.0.0.0/8".to_owned()];
+    settings.rate_limit.rules = vec![RateLimitRule {
+        route: route.to_owned(),
+        methods: Vec::new(),
+        key: key.to_owned(),
+        requests,
+        period_secs: 60,
+    }];
+    Arc::new(settings)
+}
+
+fn from(peer: &str) -> test::TestRequest {
+    test::TestRequest::default().peer_addr(peer.parse::<SocketAddr>().unwrap())
+}
+
+fn login(peer: &str) -> test::TestRequest {
+    from(peer)
+        .method(actix_web::http::Method::POST)
+        .uri("/a/login")
+        .set_json(json!({"email": "nobody@c12.io", "password": "wrong-password"}))
+}
+
+#[actix_web::test]
+async fn clients_over_the_limit_get_429() {
+    let app = test::init_service(create_app(settings("/a/login", "ip", 2), Repositories::in_memory())).await;
+
+    let resp = test::call_service(&app, login("198.51.100.7:4000").to_request()).await;
+    assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "2");
+    assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "1");
+    assert_eq!(resp.headers().get("ratelimit-policy").unwrap(), "2;w=60");
+    test::call_service(&app, login("198.51.100.7:4001").to_request()).await;
+
+    let resp = test::call_service(&app, login("198.51.100.7:4002").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "0");
+    let retry_after: u64 = resp.headers().get(header::RETRY_AFTER).unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=30).contains(&retry_after));
+    let body = common::read_json(resp).await;
+    assert_eq!(body["status"], 429);
+    assert_eq!(body["message"], "Too many requests.");
+
+    // Other clients and other routes keep their own budget.
+    let resp = test::call_service(&app, login("198.51.100.8:4000").to_request()).await;
+    assert_ne!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = test::call_service(&app, from("198.51.100.7:4003").uri("/ping").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("ratelimit-limit").is_none());
+}
+
+#[actix_web::test]
+async fn forwarded_addresses_are_only_believed_from_trusted_proxies() {
+    let app = test::init_service(create_app(settings("/ping", "ip", 1), Repositories::in_memory())).await;
+    let ping = |peer: &str, forwarded: &str| {
+        from(peer)
+            .uri("/ping")
+            .insert_header(("X-Forwarded-For", forwarded.to_owned()))
+            .to_request()
+    };
+
+    // The same client through two chains of trusted proxies shares one bucket.
+    let resp = test::call_service(&app, ping("127.0.0.1:5000", "203.0.113.9")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, ping("127.0.0.1:5001", "203.0.113.9, 10.1.2.3")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // An untrusted peer can not pick its own address.
+    let resp = test::call_service(&app, ping("198.51.100.1:5000", "203.0.113.10")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, ping("198.51.100.1:5001", "203.0.113.11")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn account_rules_are_keyed_by_the_token_subject() {
+    let app = test::init_service(create_app(settings("/api/*", "account", 1), Repositories::in_memory())).await;
+    let hello = |account: &str| {
+        from("198.51.100.7:4000")
+            .uri("/api/hello")
+            .insert_header(common::account_bearer(account, &["ROLE_USER"]))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, hello("alice")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, hello("alice")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    // Another account behind the same address is not affected.
+    assert_eq!(test::call_service(&app, hello("bob")).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn trusted_proxies_are_addresses_or_cidr_ranges() {
+    let range: IpRange = "10.0.0.0/8".parse().unwrap();
+    assert!(range.contains("10.200.3.4".parse().unwrap()));
+    assert!(!range.contains("11.0.0.1".parse().unwrap()));
+    // IPv4 peers seen through an IPv6 socket still match.
+    assert!(range.contains("::ffff:10.0.0.1".parse().unwrap()));
+
+    let range: IpRange = "2001:db8::/32".parse().unwrap();
+    assert!(range.contains("2001:db8:1::1".parse().unwrap()));
+    assert!(!range.contains("10.0.0.1".parse().unwrap()));
+
+    assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    assert!("proxy.c12.io".parse::<IpRange>().is_err());
+}
+
+#[actix_web::test]
+async fn invalid_rules_are_rejected() {
+    let path = std::env::temp_dir().join("actix-api-rate-limit-test.toml");
+    std::fs::write(
+        &path,
+        "[rate_limit]\nstore = \"redis\"\ntrusted_proxies = [\"10.0.0.0/40\"]\n\n\
+         [[rate_limit.rules]]\nroute = \"api/*\"\nkey = \"session\"\nrequests = 0\nperiod_secs = 60\n",
+    )
+    .unwrap();
+    let err = Settings::build(path.to_str(), Some(Default::default())).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    let SettingsError::Invalid { errors } = err else {
+        panic!("expected validation errors, got {}", err);
+    };
+    assert!(errors.iter().any(|e| e.starts_with("rate_limit.store:")));
+    assert!(errors.contains(&"rate_limit.trusted_proxies: 10.0.0.0/40 is not an address or a CIDR range".to_owned()));
+    assert!(errors.iter().any(|e| e.starts_with("rate_limit.rules[0].route:")));
+    assert!(errors.iter().any(|e| e.starts_with("rate_limit.rules[0].key:")));
+    assert!(errors.iter().any(|e| e.starts_with("rate_limit.rules[0].requests:")));
+}
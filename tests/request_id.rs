@@ -0,0 +1,58 @@
+mod common;
+
+use actix_api::app::{create_app, Repositories};
+use actix_web::http::StatusCode;
+use actix_web::test;
+
+use common::{read_json, user_bearer};
+
+#[actix_web::test]
+async fn client_request_id_is_echoed_and_in_errors() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/missing")
+        .insert_header(user_bearer())
+        .insert_header(("X-Request-Id", "checkout-42"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "checkout-42");
+    let body = read_json(resp).await;
+    assert_eq!(body["request_id"], "checkout-42");
+}
+
+#[actix_web::test]
+async fn request_id_is_generated_when_missing_or_invalid() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get().uri("/health/live").to_request();
+    let resp = test::call_service(&app, req).await;
+    let generated = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_owned();
+    assert!(!generated.is_empty());
+
+    let too_long = "x".repeat(200);
+    let req = test::TestRequest::get()
+        .uri("/health/live")
+        .insert_header(("X-Request-Id", too_long.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let replaced = resp.headers().get("x-request-id").unwrap().to_str().unwrap();
+    assert_ne!(replaced, too_long);
+    assert_ne!(replaced, generated);
+}
+
+#[actix_web::test]
+async fn request_id_is_echoed_on_middleware_errors() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    // The bearer middleware rejects the token before any handler runs.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks")
+        .insert_header(("Authorization", "Bearer not-a-jwt"))
+        .insert_header(("X-Request-Id", "bad-token-1"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "bad-token-1");
+}
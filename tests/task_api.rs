@@ -0,0 +1,762 @@
+mod common;
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::models::page_response::PageBuilder;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::json;
+
+use common::{read_json, user_bearer};
+
+#[actix_web::test]
+async fn task_crud_round_trip() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"1\"");
+    let task = read_json(resp).await;
+    let id = task["_id"].as_str().unwrap().to_owned();
+    assert_eq!(task["title"], "Write tests");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_json(resp).await["body"], "Cover every route of the API");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(json!({"title": "Write more tests"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_json(resp).await["title"], "Write more tests");
+
+    // The task moved on to version 2, the old ETag no longer matches.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/tasks/{}/restore", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn missing_task_is_not_found() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/does-not-exist")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body = read_json(resp).await;
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["message"], "Task not found for the given ID");
+
+    let req = test::TestRequest::put()
+        .uri("/api/tasks/does-not-exist")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri("/api/tasks/does-not-exist")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn invalid_task_has_validation_sub_errors() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Tiny", "body": "Cover every route of the API"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = read_json(resp).await;
+    assert_eq!(body["status"], 422);
+    assert_eq!(body["message"], "Validation error on field");
+    let sub_errors = body["sub_errors"].as_array().unwrap();
+    assert_eq!(sub_errors.len(), 1);
+    assert_eq!(sub_errors[0]["object"], "Task");
+    assert_eq!(sub_errors[0]["field"], "title");
+    assert_eq!(sub_errors[0]["rejected_value"], "\"Tiny\"");
+    assert_eq!(sub_errors[0]["message"], "Title must have minimum of 5 characters");
+}
+
+#[actix_web::test]
+async fn malformed_json_payloads_are_rejected() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    // Wrong content type.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .set_payload("title=Write tests")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(read_json(resp).await["message"], "Unsupported media type");
+
+    // Not JSON at all.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload("{\"title\": ")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(read_json(resp).await["status"], 400);
+
+    // Valid JSON of the wrong shape.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": 42}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = read_json(resp).await;
+    assert_eq!(body["message"], "Unprocessable payload");
+    // The serde error is not sent by default.
+    assert!(body["debug_message"].is_null());
+}
+
+#[actix_web::test]
+async fn task_list_has_offset_and_cursor_links() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for n in 1..=5 {
+        let req = test::TestRequest::post()
+            .uri("/api/tasks")
+            .insert_header(user_bearer())
+            .set_json(json!({"title": format!("Task number {}", n), "body": "Some task to page through"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks?offset=2&limit=2")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = read_json(resp).await;
+    assert_eq!(page["data"].as_array().unwrap().len(), 2);
+    assert_eq!(page["meta"]["offset"], 2);
+    assert_eq!(page["meta"]["total_results"], 5);
+    assert_eq!(page["_link"]["first"]["href"], "/api/tasks?offset=0&limit=2");
+    assert_eq!(page["_link"]["previous"]["href"], "/api/tasks?offset=0&limit=2");
+    assert_eq!(page["_link"]["next"]["href"], "/api/tasks?offset=4&limit=2");
+    assert_eq!(page["_link"]["last"]["href"], "/api/tasks?offset=4&limit=2");
+
+    // Walk every cursor page forward.
+    let mut titles = Vec::new();
+    let mut uri = "/api/tasks?after=&limit=2".to_owned();
+    loop {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(user_bearer())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page = read_json(resp).await;
+        assert!(page["meta"].get("offset").is_none());
+        for task in page["data"].as_array().unwrap() {
+            titles.push(task["title"].as_str().unwrap().to_owned());
+        }
+        match page["_link"]["next"]["href"].as_str() {
+            Some(next) => uri = next.to_owned(),
+            None => break,
+        }
+    }
+    let mut sorted = titles.clone();
+    sorted.sort();
+    assert_eq!(titles.len(), 5);
+    assert_eq!(titles, sorted);
+}
+
+#[actix_web::test]
+async fn stats_count_tasks_of_the_range() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let tasks = [
+        json!({"title": "Write tests", "body": "Cover every route of the API", "status": "done", "tags": ["api", "test"], "assignee": "ada"}),
+        json!({"title": "Write docs", "body": "Describe every route of the API", "status": "in_progress", "tags": ["api"], "assignee": "ada"}),
+        json!({"title": "Ship it", "body": "Release the first version", "tags": ["release"]}),
+    ];
+    for task in tasks {
+        let req = test::TestRequest::post()
+            .uri("/api/tasks")
+            .insert_header(user_bearer())
+            .set_json(task)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/stats")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let stats = read_json(resp).await;
+    assert_eq!(stats["by_status"].as_array().unwrap().len(), 3);
+    assert_eq!(stats["by_tag"][0], json!({"key": "api", "count": 2}));
+    assert_eq!(stats["by_assignee"], json!([{"key": "ada", "count": 2}]));
+    let days = stats["created_per_day"].as_array().unwrap();
+    assert_eq!(days.len(), 30);
+    assert_eq!(days[29]["key"], stats["to"]);
+    assert_eq!(days[29]["count"], 3);
+    assert_eq!(days[0]["count"], 0);
+
+    // Tasks created before the range are not counted.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/stats?from=2020-01-01&to=2020-01-07")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let stats = read_json(resp).await;
+    assert!(stats["by_status"].as_array().unwrap().is_empty());
+    assert_eq!(stats["created_per_day"].as_array().unwrap().len(), 7);
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/stats?from=2020-02-01&to=2020-01-01")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn put_and_patch_clear_the_assignee() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API", "status": "done", "assignee": "ada"}))
+        .to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    let uri = format!("/api/tasks/{}", task["_id"].as_str().unwrap());
+    assert_eq!(task["assignee"], "ada");
+
+    // PUT replaces the whole task, missing members are reset.
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let task = read_json(resp).await;
+    assert_eq!(task["status"], "todo");
+    assert!(task.get("assignee").is_none());
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(user_bearer())
+        .set_json(json!({"assignee": "grace"}))
+        .to_request();
+    assert_eq!(read_json(test::call_service(&app, req).await).await["assignee"], "grace");
+
+    // PATCH removes it with null, leaving the other members alone.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(user_bearer())
+        .set_json(json!({"assignee": null}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let task = read_json(resp).await;
+    assert!(task.get("assignee").is_none());
+    assert_eq!(task["title"], "Write tests");
+}
+
+#[actix_web::test]
+async fn patch_null_only_removes_optional_members() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API", "assignee": "ada"}))
+        .to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    let uri = format!("/api/tasks/{}", task["_id"].as_str().unwrap());
+
+    // A required member can not be removed, nothing is written.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(user_bearer())
+        .set_json(json!({"title": null, "assignee": null}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::get().uri(&uri).insert_header(user_bearer()).to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(task["title"], "Write tests");
+    assert_eq!(task["assignee"], "ada");
+    assert_eq!(task["version"], 1);
+}
+
+#[actix_web::test]
+async fn export_then_import_keeps_every_task_field() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API", "status": "in_progress", "tags": ["api", "test"], "assignee": "ada"}))
+        .to_request();
+    let original = read_json(test::call_service(&app, req).await).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/export?format=csv")
+        .insert_header(user_bearer())
+        .to_request();
+    let export = test::read_body(test::call_service(&app, req).await).await;
+    let mut lines = std::str::from_utf8(&export).unwrap().lines();
+    assert_eq!(lines.next(), Some("id,title,body,status,tags,assignee,created_at"));
+
+    // Imported into a fresh store, the stats see the same task.
+    let other = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/import?format=csv")
+        .insert_header(user_bearer())
+        .set_payload(export)
+        .to_request();
+    let resp = test::call_service(&other, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let imported = read_json(resp).await;
+    assert_eq!(imported["imported"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", imported["results"][0]["id"].as_str().unwrap()))
+        .insert_header(user_bearer())
+        .to_request();
+    let task = read_json(test::call_service(&other, req).await).await;
+    assert_ne!(task["_id"], original["_id"]);
+    for field in ["title", "body", "status", "tags", "assignee", "created_at"] {
+        assert_eq!(task[field], original[field], "{}", field);
+    }
+}
+
+#[actix_web::test]
+async fn tasks_export_as_ndjson() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for title in ["Write tests", "Ship release"] {
+        let req = test::TestRequest::post()
+            .uri("/api/tasks")
+            .insert_header(user_bearer())
+            .set_json(json!({"title": title, "body": "Cover every route of the API", "tags": ["api"]}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/export?format=ndjson")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-ndjson");
+    let disposition = resp.headers().get(header::CONTENT_DISPOSITION).unwrap();
+    assert!(disposition.to_str().unwrap().contains("tasks.ndjson"));
+    let export = test::read_body(resp).await;
+    let mut titles: Vec<String> = std::str::from_utf8(&export)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .map(|task| {
+            assert_eq!(task["tags"], "api");
+            task["title"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Ship release", "Write tests"]);
+
+    // Unknown formats are refused.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/export?format=xml")
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn task_import_reports_every_row() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    // The format comes from the Content-Type, valid rows are kept.
+    let upload = concat!(
+        "{\"title\": \"Write tests\", \"body\": \"Cover every route of the API\", \"tags\": \"api;test\"}\n",
+        "{\"title\": \n",
+        "\n",
+        "{\"title\": \"Tiny\", \"body\": \"Cover every route of the API\"}\n",
+        "{\"title\": \"Ship release\", \"body\": \"Tag and publish the crate\", \"created_at\": \"yesterday\"}\n",
+    );
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/import")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+        .set_payload(upload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = read_json(resp).await;
+    assert_eq!(body["imported"], 1);
+    assert_eq!(body["failed"], 3);
+    let statuses: Vec<u64> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_u64().unwrap())
+        .collect();
+    assert_eq!(statuses, vec![201, 400, 422, 400]);
+    assert!(body["results"][0]["id"].is_string());
+    assert_eq!(body["results"][2]["error"]["sub_errors"][0]["field"], "title");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", body["results"][0]["id"].as_str().unwrap()))
+        .insert_header(user_bearer())
+        .to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(task["tags"], json!(["api", "test"]));
+
+    // CSV rows are checked the same way, `format` wins over the Content-Type.
+    let upload = "title,body,assignee\r\nShip release,Tag and publish the crate,\r\nTiny,Too short\r\n";
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/import?format=csv")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/json"))
+        .set_payload(upload)
+        .to_request();
+    let body = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["imported"], 1);
+    assert_eq!(body["results"][1]["status"], 400);
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks?limit=10")
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(read_json(test::call_service(&app, req).await).await["meta"]["total_results"], 2);
+
+    // Without a known format the upload is refused.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/import")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "text/plain"))
+        .set_payload(upload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+// Statuses of the bulk results, in request order.
+fn bulk_statuses(body: &serde_json::Value) -> Vec<u64> {
+    body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_u64().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn bulk_reports_each_operation() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/bulk")
+        .insert_header(user_bearer())
+        .set_json(json!({"operations": [
+            {"op": "create", "task": {"title": "Ship release", "body": "Tag and publish the crate"}},
+            {"op": "create", "task": {"title": "Tiny", "body": "Tag and publish the crate"}},
+            {"op": "update", "id": id, "version": 1, "task": {"title": "Write more tests", "body": "Cover every route of the API"}},
+            {"op": "delete", "id": id, "version": 1},
+            {"op": "delete", "id": "does-not-exist"},
+        ]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = read_json(resp).await;
+    assert_eq!(body["atomic"], false);
+    assert_eq!(bulk_statuses(&body), vec![201, 422, 200, 412, 404]);
+    assert_eq!(body["results"][1]["error"]["sub_errors"][0]["field"], "title");
+    assert_eq!(body["results"][2]["id"], id.as_str());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", body["results"][0]["id"].as_str().unwrap()))
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(task["title"], "Write more tests");
+    assert_eq!(task["version"], 2);
+}
+
+#[actix_web::test]
+async fn atomic_bulk_writes_nothing_when_an_operation_fails() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    // An invalid task aborts the batch before any write.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/bulk?atomic=true")
+        .insert_header(user_bearer())
+        .set_json(json!({"operations": [
+            {"op": "create", "task": {"title": "Ship release", "body": "Tag and publish the crate"}},
+            {"op": "create", "task": {"title": "Tiny", "body": "Tag and publish the crate"}},
+        ]}))
+        .to_request();
+    let body = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["atomic"], true);
+    assert_eq!(bulk_statuses(&body), vec![424, 422]);
+
+    // A stale version rolls back the writes before it.
+    let req = test::TestRequest::post()
+        .uri("/api/tasks/bulk?atomic=true")
+        .insert_header(user_bearer())
+        .set_json(json!({"operations": [
+            {"op": "create", "task": {"title": "Ship release", "body": "Tag and publish the crate"}},
+            {"op": "update", "id": id, "task": {"title": "Write more tests", "body": "Cover every route of the API"}},
+            {"op": "delete", "id": id, "version": 7},
+        ]}))
+        .to_request();
+    let body = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(bulk_statuses(&body), vec![424, 424, 412]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks?limit=10")
+        .insert_header(user_bearer())
+        .to_request();
+    let page = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(page["meta"]["total_results"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let task = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(task["title"], "Write tests");
+    assert_eq!(task["version"], 1);
+}
+
+#[actix_web::test]
+async fn get_task_honours_if_none_match() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_NONE_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"1\"");
+    assert!(test::read_body(resp).await.is_empty());
+
+    // After a write the cached version is stale.
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write more tests"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tasks/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_NONE_MATCH, "\"1\""))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"2\"");
+    assert_eq!(read_json(resp).await["title"], "Write more tests");
+}
+
+#[actix_web::test]
+async fn task_cursor_pages_walk_backward() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for n in 1..=5 {
+        let req = test::TestRequest::post()
+            .uri("/api/tasks")
+            .insert_header(user_bearer())
+            .set_json(json!({"title": format!("Task number {}", n), "body": "Some task to page through"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    // An empty `before` starts at the last page, `previous` links lead back to the first.
+    let mut pages: Vec<Vec<String>> = Vec::new();
+    let mut uri = "/api/tasks?before=&limit=2&count=true".to_owned();
+    loop {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(user_bearer())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page = read_json(resp).await;
+        assert_eq!(page["meta"]["total_results"], 5);
+        if pages.is_empty() {
+            assert!(page["_link"]["next"].is_null());
+        } else {
+            assert!(page["_link"]["next"]["href"].as_str().unwrap().contains("after="));
+        }
+        let titles = page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|task| task["title"].as_str().unwrap().to_owned())
+            .collect();
+        pages.push(titles);
+        match page["_link"]["previous"]["href"].as_str() {
+            Some(previous) => uri = previous.to_owned(),
+            None => break,
+        }
+    }
+    let sizes: Vec<usize> = pages.iter().map(Vec::len).collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+    let titles: Vec<String> = pages.into_iter().rev().flatten().collect();
+    let expected: Vec<String> = (1..=5).map(|n| format!("Task number {}", n)).collect();
+    assert_eq!(titles, expected);
+}
+
+#[actix_web::test]
+async fn limits_above_the_maximum_are_rejected() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for query in [
+        "limit=101",
+        "after=&limit=9223372036854775807",
+        "before=&limit=9223372036854775807",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/tasks?{}", query))
+            .insert_header(user_bearer())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[actix_web::test]
+async fn offsets_past_the_end_are_rejected() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks?offset=18446744073709551615&limit=100")
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // An offset at the end of the collection is an empty last page.
+    let req = test::TestRequest::get()
+        .uri("/api/tasks?offset=0")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = read_json(resp).await;
+    assert!(page["data"].as_array().unwrap().is_empty());
+    assert!(page["_link"]["next"].is_null());
+}
+
+#[actix_web::test]
+async fn page_links_stop_at_the_largest_offset() {
+    let req = test::TestRequest::get().uri("/api/tasks").to_http_request();
+    let page = PageBuilder::new(&req, 100)
+        .offset(u64::MAX)
+        .total(Some(u64::MAX))
+        .build(Vec::<()>::new());
+    assert!(page._link.next.is_none());
+    assert!(page._link.previous.is_some());
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use actix_api::config::settings::TlsSettings;
+use actix_api::config::tls;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+
+struct Issued {
+    cert: CertificateDer<'static>,
+    key: PrivateKeyDer<'static>,
+}
+
+// Self-signed certificate for localhost, written to `<name>.pem` and `<name>.key`.
+fn self_signed(dir: &Path, name: &str) -> Issued {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+    fs::write(dir.join(format!("{}.pem", name)), certified.cert.pem()).unwrap();
+    fs::write(dir.join(format!("{}.key", name)), certified.key_pair.serialize_pem()).unwrap();
+    Issued {
+        cert: certified.cert.der().clone(),
+        key: PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()).into(),
+    }
+}
+
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("actix-api-tls-{}", test));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn settings(dir: &Path, name: &str) -> TlsSettings {
+    TlsSettings {
+        enabled: true,
+        cert_path: dir.join(format!("{}.pem", name)).to_str().unwrap().to_owned(),
+        key_path: dir.join(format!("{}.key", name)).to_str().unwrap().to_owned(),
+        ..Default::default()
+    }
+}
+
+fn client(trusted: &CertificateDer<'static>, identity: Option<&Issued>) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add(trusted.clone()).unwrap();
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+    match identity {
+        Some(issued) => builder
+            .with_client_auth_cert(vec![issued.cert.clone()], issued.key.clone_key())
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    }
+}
+
+// Runs a whole handshake in memory, failing on the first error of either side.
+fn handshake(server: ServerConfig, client: ClientConfig) -> Result<(), rustls::Error> {
+    let mut server = ServerConnection::new(Arc::new(server))?;
+    let mut client = ClientConnection::new(Arc::new(client), ServerName::try_from("localhost").unwrap())?;
+    for _ in 0..10 {
+        let mut buf = Vec::new();
+        while client.wants_write() {
+            client.write_tls(&mut buf).unwrap();
+        }
+        server.read_tls(&mut buf.as_slice()).unwrap();
+        server.process_new_packets()?;
+
+        let mut buf = Vec::new();
+        while server.wants_write() {
+            server.write_tls(&mut buf).unwrap();
+        }
+        client.read_tls(&mut buf.as_slice()).unwrap();
+        client.process_new_packets()?;
+
+        if !client.is_handshaking() && !server.is_handshaking() {
+            return Ok(());
+        }
+    }
+    panic!("handshake did not complete");
+}
+
+#[actix_web::test]
+async fn certificate_is_reloaded_from_disk() {
+    let dir = temp_dir("reload");
+    let first = self_signed(&dir, "server");
+    let (config, resolver) = tls::server_config(&settings(&dir, "server")).unwrap();
+    assert_eq!(resolver.current().end_entity_cert().unwrap(), &first.cert);
+    handshake(config.clone(), client(&first.cert, None)).unwrap();
+
+    // New connections get the new certificate, the same config is kept.
+    let second = self_signed(&dir, "server");
+    resolver.reload().unwrap();
+    assert_eq!(resolver.current().end_entity_cert().unwrap(), &second.cert);
+    handshake(config.clone(), client(&second.cert, None)).unwrap();
+    assert!(handshake(config.clone(), client(&first.cert, None)).is_err());
+
+    // A broken file is reported and the previous certificate stays.
+    fs::write(dir.join("server.pem"), "not a certificate").unwrap();
+    assert!(resolver.reload().is_err());
+    handshake(config, client(&second.cert, None)).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn client_certificates_are_verified_against_the_ca() {
+    let dir = temp_dir("mtls");
+    let server = self_signed(&dir, "server");
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+
+    let caller_key = KeyPair::generate().unwrap();
+    let caller = CertificateParams::new(vec!["billing".to_owned()])
+        .unwrap()
+        .signed_by(&caller_key, &ca, &ca_key)
+        .unwrap();
+    let caller = Issued {
+        cert: caller.der().clone(),
+        key: PrivatePkcs8KeyDer::from(caller_key.serialize_der()).into(),
+    };
+    let stranger = self_signed(&dir, "stranger");
+
+    let mut settings = settings(&dir, "server");
+    settings.client_ca_path = Some(dir.join("ca.pem").to_str().unwrap().to_owned());
+    settings.client_cert_required = true;
+    let (required, _) = tls::server_config(&settings).unwrap();
+    handshake(required.clone(), client(&server.cert, Some(&caller))).unwrap();
+    assert!(handshake(required.clone(), client(&server.cert, Some(&stranger))).is_err());
+    assert!(handshake(required, client(&server.cert, None)).is_err());
+
+    // Optional verification still rejects certificates from other issuers.
+    settings.client_cert_required = false;
+    let (optional, _) = tls::server_config(&settings).unwrap();
+    handshake(optional.clone(), client(&server.cert, None)).unwrap();
+    assert!(handshake(optional, client(&server.cert, Some(&stranger))).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[actix_web::test]
+async fn missing_files_are_reported() {
+    let dir = temp_dir("missing");
+    let err = tls::server_config(&settings(&dir, "absent")).unwrap_err();
+    assert!(err.to_string().contains("absent.pem"));
+    fs::remove_dir_all(&dir).unwrap();
+}
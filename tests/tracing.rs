@@ -0,0 +1,55 @@
+mod common;
+
+use std::future::{ready, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use actix_api::app::{create_app, Repositories};
+use actix_api::telemetry;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use opentelemetry::trace::SpanKind;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::trace::TracerProvider;
+
+use common::user_bearer;
+
+// Keeps every exported span for the assertions.
+#[derive(Clone, Debug, Default)]
+struct Collected(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for Collected {
+    fn export(&mut self, batch: Vec<SpanData>) -> Pin<Box<dyn Future<Output = ExportResult> + Send>> {
+        self.0.lock().unwrap().extend(batch);
+        Box::pin(ready(Ok(())))
+    }
+}
+
+#[actix_web::test]
+async fn inbound_traceparent_is_continued() {
+    let spans = Collected::default();
+    telemetry::install(TracerProvider::builder().with_simple_exporter(spans.clone()).build());
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/tasks/missing")
+        .insert_header(user_bearer())
+        .insert_header(("traceparent", "00-4bf92f3577b34a4e708a7e1f2d1f8a3d-00f067aa0ba902b7-01"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    let spans = spans.0.lock().unwrap();
+    let server = spans.iter().find(|span| span.span_kind == SpanKind::Server).unwrap();
+    assert_eq!(server.name, "GET /api/tasks/{id}");
+    assert_eq!(server.span_context.trace_id().to_string(), "4bf92f3577b34a4e708a7e1f2d1f8a3d");
+    assert_eq!(server.parent_span_id.to_string(), "00f067aa0ba902b7");
+    assert!(server
+        .attributes
+        .contains(&KeyValue::new("http.response.status_code", 404_i64)));
+
+    // The service call is a child of the request span.
+    let service = spans.iter().find(|span| span.name == "task_service.get_task_by_id").unwrap();
+    assert_eq!(service.span_context.trace_id(), server.span_context.trace_id());
+    assert_eq!(service.parent_span_id, server.span_context.span_id());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use actix_api::app::{create_app, Repositories};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::json;
+
+use common::{read_json, user_bearer};
+
+#[actix_web::test]
+async fn trash_lists_deleted_tasks_and_users() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/tasks")
+        .insert_header(user_bearer())
+        .set_json(json!({"title": "Write tests", "body": "Cover every route of the API"}))
+        .to_request();
+    let task_id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let req = test::TestRequest::post()
+        .uri("/api/users")
+        .insert_header(user_bearer())
+        .set_json(json!({"name": "Ada", "location": "London", "title": "Engineer"}))
+        .to_request();
+    let user_id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    for uri in [format!("/api/tasks/{}", task_id), format!("/api/users/{}", user_id)] {
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(user_bearer())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/trash?limit=10")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = read_json(resp).await;
+    assert_eq!(page["meta"]["total_results"], 2);
+    let mut items: Vec<(String, String, String)> = page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            assert!(item["deleted_at"].is_string());
+            (
+                item["kind"].as_str().unwrap().to_owned(),
+                item["id"].as_str().unwrap().to_owned(),
+                item["name"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect();
+    items.sort();
+    assert_eq!(
+        items,
+        vec![
+            ("task".to_owned(), task_id.clone(), "Write tests".to_owned()),
+            ("user".to_owned(), user_id, "Ada".to_owned()),
+        ]
+    );
+
+    // Restored items leave the trash.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/tasks/{}/restore", task_id))
+        .insert_header(user_bearer())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri("/api/trash?offset=0&limit=1")
+        .insert_header(user_bearer())
+        .to_request();
+    let page = read_json(test::call_service(&app, req).await).await;
+    assert_eq!(page["meta"]["total_results"], 1);
+    assert_eq!(page["data"][0]["kind"], "user");
+
+    let req = test::TestRequest::get().uri("/api/trash").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use actix_api::app::{create_app, Repositories};
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::json;
+
+use common::{read_json, user_bearer};
+
+#[actix_web::test]
+async fn user_create_get_and_list() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users")
+        .insert_header(user_bearer())
+        .set_json(json!({"name": "Ada", "location": "London", "title": "Engineer"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let user = read_json(resp).await;
+    let id = user["_id"].as_str().unwrap().to_owned();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_json(resp).await["name"], "Ada");
+
+    let req = test::TestRequest::get()
+        .uri("/api/users?limit=10")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = read_json(resp).await;
+    assert_eq!(page["data"][0]["id"], id.as_str());
+    assert_eq!(page["meta"]["total_results"], 1);
+    assert!(page["_link"]["next"].is_null());
+    assert!(page["_link"]["previous"].is_null());
+}
+
+#[actix_web::test]
+async fn invalid_and_missing_users() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users")
+        .insert_header(user_bearer())
+        .set_json(json!({"name": "A", "location": "Somewhere far too long", "title": "Engineer"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = read_json(resp).await;
+    let mut fields: Vec<&str> = body["sub_errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    fields.sort();
+    assert_eq!(fields, vec!["location", "name"]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/does-not-exist")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(read_json(resp).await["message"], "User not found for the given ID");
+}
+
+#[actix_web::test]
+async fn users_export_and_import() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users")
+        .insert_header(user_bearer())
+        .set_json(json!({"name": "Ada", "location": "London", "title": "Engineer"}))
+        .to_request();
+    let id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/export?format=csv")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "text/csv");
+    let export = test::read_body(resp).await;
+    assert_eq!(
+        std::str::from_utf8(&export).unwrap(),
+        format!("id,name,location,title\n{},Ada,London,Engineer\n", id)
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/users/export?format=ndjson")
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-ndjson");
+    let export = test::read_body(resp).await;
+    let user: serde_json::Value = serde_json::from_slice(export.trim_ascii_end()).unwrap();
+    assert_eq!(user["name"], "Ada");
+
+    // Valid rows are imported next to the failing ones.
+    let upload = "name,location,title\r\nGrace,Arlington,Admiral\r\nL,Nowhere,Engineer\r\nAlan\r\n";
+    let req = test::TestRequest::post()
+        .uri("/api/users/import")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "text/csv"))
+        .set_payload(upload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = read_json(resp).await;
+    assert_eq!(body["imported"], 1);
+    assert_eq!(body["failed"], 2);
+    assert_eq!(body["results"][0]["status"], 201);
+    assert_eq!(body["results"][1]["status"], 422);
+    assert_eq!(body["results"][1]["error"]["sub_errors"][0]["field"], "name");
+    assert_eq!(body["results"][2]["status"], 400);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}", body["results"][0]["id"].as_str().unwrap()))
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(read_json(test::call_service(&app, req).await).await["name"], "Grace");
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/import")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/jsonl"))
+        .set_payload("{\"name\": \"Alan\", \"location\": \"Wilmslow\", \"title\": \"Mathematician\"}\n")
+        .to_request();
+    assert_eq!(read_json(test::call_service(&app, req).await).await["imported"], 1);
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/import")
+        .insert_header(user_bearer())
+        .insert_header((header::CONTENT_TYPE, "application/xml"))
+        .set_payload("<users/>")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get()
+        .uri("/api/users?limit=10")
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(read_json(test::call_service(&app, req).await).await["meta"]["total_results"], 3);
+}
+
+#[actix_web::test]
+async fn user_patch_delete_and_restore() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users")
+        .insert_header(user_bearer())
+        .set_json(json!({"name": "Ada", "location": "London", "title": "Engineer"}))
+        .to_request();
+    let id = read_json(test::call_service(&app, req).await).await["_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(json!({"title": "Lead Engineer"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"2\"");
+    let user = read_json(resp).await;
+    assert_eq!(user["title"], "Lead Engineer");
+    assert_eq!(user["location"], "London");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .set_json(json!({"location": "L"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_NONE_MATCH, "\"2\""))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .insert_header((header::IF_MATCH, "\"2\""))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/users/{}/restore", id))
+        .insert_header(user_bearer())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/users/{}", id))
+        .insert_header(user_bearer())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_json(resp).await["title"], "Lead Engineer");
+
+    // Only deleted users can be restored.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/users/{}/restore", id))
+        .insert_header(user_bearer())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn limits_above_the_maximum_are_rejected() {
+    let app = test::init_service(create_app(Default::default(), Repositories::in_memory())).await;
+
+    for query in [
+        "limit=101",
+        "after=&limit=9223372036854775807",
+        "before=&limit=9223372036854775807",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/users?{}", query))
+            .insert_header(user_bearer())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+}